@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// One host of the Ansible inventory, built while processing items
+#[derive(Debug, Clone)]
+pub struct InventoryHost {
+    /// Vault the item came from (becomes the group)
+    pub vault: String,
+    /// Inventory host name (the item's first alias, like the rclone remote)
+    pub name: String,
+    /// Address to connect to (`ansible_host`)
+    pub host: String,
+    pub user: Option<String>,
+    pub port: Option<String>,
+    /// Absolute path of the extracted private key
+    pub key_file: Option<String>,
+}
+
+impl InventoryHost {
+    fn vars(&self) -> Vec<(&'static str, &str)> {
+        let mut vars = vec![("ansible_host", self.host.as_str())];
+        if let Some(ref user) = self.user {
+            vars.push(("ansible_user", user));
+        }
+        if let Some(ref port) = self.port {
+            vars.push(("ansible_port", port));
+        }
+        if let Some(ref key_file) = self.key_file {
+            vars.push(("ansible_ssh_private_key_file", key_file));
+        }
+        vars
+    }
+}
+
+/// Turn a vault name into a valid Ansible group name (letters, digits, `_`)
+fn group_name(vault: &str) -> String {
+    let name: String = vault
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) || name.is_empty() {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+/// Hosts grouped by Ansible group, each sorted by name
+fn grouped(hosts: &[InventoryHost]) -> BTreeMap<String, Vec<&InventoryHost>> {
+    let mut groups: BTreeMap<String, Vec<&InventoryHost>> = BTreeMap::new();
+    for host in hosts {
+        groups
+            .entry(group_name(&host.vault))
+            .or_default()
+            .push(host);
+    }
+    for group in groups.values_mut() {
+        group.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+    groups
+}
+
+/// Render an INI inventory: one `[group]` per vault
+pub fn render_ini(hosts: &[InventoryHost]) -> String {
+    let mut content = String::from("# Generated by pass-ssh-unpack\n");
+    for (group, hosts) in grouped(hosts) {
+        content.push_str(&format!("\n[{}]\n", group));
+        for host in hosts {
+            content.push_str(&host.name);
+            for (key, value) in host.vars() {
+                if value.contains(char::is_whitespace) {
+                    content.push_str(&format!(" {}=\"{}\"", key, value));
+                } else {
+                    content.push_str(&format!(" {}={}", key, value));
+                }
+            }
+            content.push('\n');
+        }
+    }
+    content
+}
+
+/// Render a YAML inventory: one child group of `all` per vault
+pub fn render_yaml(hosts: &[InventoryHost]) -> String {
+    // JSON strings are valid YAML scalars, which takes care of quoting
+    let quote = |s: &str| serde_json::to_string(s).unwrap_or_default();
+
+    let mut content = String::from("# Generated by pass-ssh-unpack\nall:\n  children:\n");
+    for (group, hosts) in grouped(hosts) {
+        content.push_str(&format!("    {}:\n      hosts:\n", group));
+        for host in hosts {
+            content.push_str(&format!("        {}:\n", quote(&host.name)));
+            for (key, value) in host.vars() {
+                // Numbers (the port) stay unquoted
+                let value = if !value.is_empty() && value.chars().all(|c| c.is_ascii_digit()) {
+                    value.to_string()
+                } else {
+                    quote(value)
+                };
+                content.push_str(&format!("          {}: {}\n", key, value));
+            }
+        }
+    }
+    content
+}
+
+/// Render the inventory in the format implied by the path (`.yml`/`.yaml` is YAML, anything else INI)
+pub fn render(path: &Path, hosts: &[InventoryHost]) -> String {
+    let is_yaml = path
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("yml") || ext.eq_ignore_ascii_case("yaml"))
+        .unwrap_or(false);
+    if is_yaml {
+        render_yaml(hosts)
+    } else {
+        render_ini(hosts)
+    }
+}
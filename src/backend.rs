@@ -0,0 +1,166 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use std::path::Path;
+
+use crate::config::FieldNames;
+use crate::proton_pass::{is_skipped_vault, ExportedItems, ProtonPass, SshItem};
+
+/// Password manager CLI to read SSH items from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum BackendKind {
+    /// Proton Pass (`pass-cli`)
+    #[default]
+    Proton,
+    /// Bitwarden (`bw`)
+    Bitwarden,
+    /// 1Password (`op`)
+    Onepassword,
+}
+
+/// Operations the runner needs from a password manager.
+/// Vault and item names are the backend's own; fields use the names
+/// documented for Proton Pass items (Host, Username, SSH, ...).
+/// Backends are shared across threads for parallel updates.
+pub trait SecretBackend: Sync {
+    /// Display name for messages
+    fn name(&self) -> &'static str;
+
+    /// List all vault names, including reserved ones like Trash
+    fn list_all_vaults(&self) -> Result<Vec<String>>;
+
+    /// List all processable items in a vault
+    fn list_all_items(&self, vault: &str, include_attachments: bool) -> Result<Vec<SshItem>>;
+
+    /// List items created by a Teleport import in a vault
+    fn list_teleport_items(&self, vault: &str) -> Result<Vec<SshItem>>;
+
+    /// List all active item titles in a vault (any type)
+    fn list_item_titles(&self, vault: &str) -> Result<Vec<String>>;
+
+    /// Get a field value from a backend-specific reference (e.g. pass://Vault/Item/password)
+    fn get_item_field(&self, path: &str) -> Result<String>;
+
+    /// Update a field of an item
+    fn update_item_field(&self, vault: &str, title: &str, field: &str, value: &str) -> Result<()>;
+
+    /// Create a new vault
+    fn create_vault(&self, name: &str) -> Result<()>;
+
+    /// Create an item for a Teleport node with SSH and Server Command fields
+    fn create_tsh_item(
+        &self,
+        vault: &str,
+        title: &str,
+        ssh_command: &str,
+        server_command: &str,
+    ) -> Result<()>;
+
+    /// Take the warnings collected since the last call (e.g. items skipped while listing)
+    fn take_warnings(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// List vault names, excluding reserved vaults in `skip` (exact, case-insensitive)
+    fn list_vaults(&self, skip: &[String]) -> Result<Vec<String>> {
+        Ok(self
+            .list_all_vaults()?
+            .into_iter()
+            .filter(|name| !is_skipped_vault(name, skip))
+            .collect())
+    }
+
+    /// Check if a vault exists by name
+    fn vault_exists(&self, name: &str) -> Result<bool> {
+        let vaults = self.list_all_vaults()?;
+        Ok(vaults.iter().any(|v| v == name))
+    }
+}
+
+/// Create the backend for `kind`, reading item values from `field_names`.
+/// Only Proton Pass is implemented so far.
+pub fn create(kind: BackendKind, field_names: FieldNames) -> Result<Box<dyn SecretBackend>> {
+    match kind {
+        BackendKind::Proton => Ok(Box::new(ProtonPass::with_field_names(field_names))),
+        BackendKind::Bitwarden | BackendKind::Onepassword => anyhow::bail!(
+            "The {} backend is not implemented yet (available: proton)",
+            kind.to_possible_value()
+                .map(|v| v.get_name().to_string())
+                .unwrap_or_default()
+        ),
+    }
+}
+
+/// Backend reading items from a JSON export (`--from-json`, "-" for stdin)
+/// instead of a password manager CLI
+pub fn from_json(path: &Path, field_names: FieldNames) -> Result<Box<dyn SecretBackend>> {
+    Ok(Box::new(ExportedItems::load(path, field_names)?))
+}
+
+/// Wrap a backend so that every write fails without running (`read_only`).
+/// Reads are passed through unchanged.
+pub fn read_only(backend: Box<dyn SecretBackend>) -> Box<dyn SecretBackend> {
+    Box::new(ReadOnly(backend))
+}
+
+struct ReadOnly(Box<dyn SecretBackend>);
+
+impl ReadOnly {
+    fn refuse(&self, action: String) -> Result<()> {
+        anyhow::bail!(
+            "Not {} in {}: read-only mode (read_only / --read-only)",
+            action,
+            self.0.name()
+        )
+    }
+}
+
+impl SecretBackend for ReadOnly {
+    fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    fn list_all_vaults(&self) -> Result<Vec<String>> {
+        self.0.list_all_vaults()
+    }
+
+    fn list_all_items(&self, vault: &str, include_attachments: bool) -> Result<Vec<SshItem>> {
+        self.0.list_all_items(vault, include_attachments)
+    }
+
+    fn take_warnings(&self) -> Vec<String> {
+        self.0.take_warnings()
+    }
+
+    fn list_teleport_items(&self, vault: &str) -> Result<Vec<SshItem>> {
+        self.0.list_teleport_items(vault)
+    }
+
+    fn list_item_titles(&self, vault: &str) -> Result<Vec<String>> {
+        self.0.list_item_titles(vault)
+    }
+
+    fn get_item_field(&self, path: &str) -> Result<String> {
+        self.0.get_item_field(path)
+    }
+
+    fn update_item_field(&self, vault: &str, title: &str, field: &str, _value: &str) -> Result<()> {
+        self.refuse(format!(
+            "updating field '{}' of '{}/{}'",
+            field, vault, title
+        ))
+    }
+
+    fn create_vault(&self, name: &str) -> Result<()> {
+        self.refuse(format!("creating vault '{}'", name))
+    }
+
+    fn create_tsh_item(
+        &self,
+        vault: &str,
+        title: &str,
+        _ssh_command: &str,
+        _server_command: &str,
+    ) -> Result<()> {
+        self.refuse(format!("creating item '{}/{}'", vault, title))
+    }
+}
@@ -1,7 +1,10 @@
 use clap::Parser;
 use std::path::PathBuf;
 
-use crate::config::SyncPublicKey;
+use crate::backend::BackendKind;
+use crate::config::{KeyFormat, KeyNaming, LabelMatch, SyncPublicKey};
+use crate::ssh::VerifySsh;
+use crate::teleport::OsFamily;
 
 /// Extract SSH keys from Proton Pass to local files and generate SSH config
 #[derive(Parser, Debug)]
@@ -16,14 +19,39 @@ pub struct Args {
     #[arg(short, long, action = clap::ArgAction::Append)]
     pub item: Vec<String>,
 
+    /// Only process items with this Proton Pass label (repeatable, see --label-match)
+    #[arg(long, value_name = "NAME", action = clap::ArgAction::Append, conflicts_with = "from_tsh")]
+    pub label: Vec<String>,
+
+    /// Override how several --label filters combine
+    #[arg(long, value_enum)]
+    pub label_match: Option<LabelMatch>,
+
     /// Full regeneration (clear config first)
     #[arg(short, long)]
     pub full: bool,
 
+    /// Only process items without a key file on disk yet; existing ones are left untouched
+    #[arg(long, conflicts_with = "full")]
+    pub only_new: bool,
+
+    /// Never write private keys to disk; hosts use their IdentityAgent or are left out
+    #[arg(long, conflicts_with_all = ["purge", "from_tsh"])]
+    pub no_write_keys: bool,
+
+    /// After writing keys, load each newly written one into the SSH agent with ssh-add
+    #[arg(long, conflicts_with_all = ["no_write_keys", "rclone", "purge", "from_tsh"])]
+    pub add_to_agent: bool,
+
     /// Suppress output
     #[arg(short, long)]
     pub quiet: bool,
 
+    /// Like --quiet, but still print the end-of-run summary (host/alias counts,
+    /// rclone changes, warnings and errors)
+    #[arg(long, conflicts_with_all = ["quiet", "json_lines", "watch"])]
+    pub summary_only: bool,
+
     /// Only process SSH keys (skip rclone sync)
     #[arg(long, conflicts_with = "rclone")]
     pub ssh: bool,
@@ -33,17 +61,137 @@ pub struct Args {
     pub rclone: bool,
 
     /// Remove all managed SSH keys and rclone remotes, then exit
+    /// (with --vault/--item/--label, only those of the matching items)
     #[arg(long)]
     pub purge: bool,
 
+    /// Remove everything this tool set up on this machine: managed SSH keys and
+    /// rclone remotes, the Include line in ~/.ssh/config and (with --remove-config)
+    /// the config file; asks for confirmation unless --yes
+    #[arg(
+        long,
+        conflicts_with_all = ["purge", "from_tsh", "vault", "item", "full", "only_new", "ssh", "rclone", "diff_ssh_config", "export_rclone_config", "watch", "json_lines", "listing", "config_check"]
+    )]
+    pub uninstall: bool,
+
+    /// Also delete the config file (with --uninstall)
+    #[arg(long, requires = "uninstall")]
+    pub remove_config: bool,
+
     /// Show what would be done without making changes
     #[arg(long)]
     pub dry_run: bool,
 
+    /// Print a unified diff of the SSH config changes without writing anything (implies --dry-run)
+    #[arg(long, conflicts_with_all = ["rclone", "purge", "from_tsh"])]
+    pub diff_ssh_config: bool,
+
+    /// Also write an Ansible inventory of the processed hosts (.yml/.yaml for YAML, else INI)
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["purge", "from_tsh"])]
+    pub ansible_inventory: Option<PathBuf>,
+
+    /// Print the managed rclone remotes as INI sections instead of syncing them
+    /// (implies --dry-run; the real rclone config is never read)
+    #[arg(long, conflicts_with_all = ["ssh", "purge", "from_tsh", "adopt_remotes"])]
+    pub export_rclone_config: bool,
+
+    /// Write --export-rclone-config output to this file instead of stdout
+    #[arg(long, requires = "export_rclone_config")]
+    pub out: Option<PathBuf>,
+
+    /// After writing the SSH config, check every generated host with `ssh -G`
+    /// (errors on a malformed stanza); `connect` also tries a batch-mode login
+    /// to each host (failures are warnings)
+    #[arg(
+        long,
+        value_enum,
+        value_name = "MODE",
+        num_args = 0..=1,
+        default_missing_value = "config",
+        conflicts_with_all = ["rclone", "purge", "from_tsh", "dry_run", "diff_ssh_config"]
+    )]
+    pub verify_ssh: Option<VerifySsh>,
+
+    /// Print a timing breakdown of the run's major phases at the end
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Keep running, re-exporting every MINUTES (default 15) and printing a
+    /// one-line summary per cycle; Ctrl-C stops
+    #[arg(
+        long,
+        value_name = "MINUTES",
+        num_args = 0..=1,
+        default_missing_value = "15",
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with_all = ["purge", "from_tsh", "dry_run", "diff_ssh_config", "export_rclone_config", "json_lines", "listing", "config_check"]
+    )]
+    pub watch: Option<u64>,
+
+    /// Stop at the first error (e.g. a failing item) instead of reporting all errors at the end
+    #[arg(long)]
+    pub fail_fast: bool,
+
+    /// Append the end-of-run summary (counts, rclone changes, warnings and errors)
+    /// to this file, even with --quiet
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["purge", "from_tsh"])]
+    pub report_file: Option<PathBuf>,
+
+    /// Stream items, rclone changes, errors and a final summary as JSON Lines on stdout (implies --quiet)
+    #[arg(long, conflicts_with_all = ["diff_ssh_config", "export_rclone_config", "from_tsh"])]
+    pub json_lines: bool,
+
+    /// Password manager CLI to read items from (only proton is implemented)
+    #[arg(long, value_enum, default_value_t = BackendKind::Proton)]
+    pub backend: BackendKind,
+
+    /// Read items from a JSON export instead of pass-cli: an object mapping each vault
+    /// name to its `pass-cli item list --output json` output ("-" reads stdin).
+    /// Nothing is written back, so public keys are never synced
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["from_tsh", "sync_public_key", "uninstall"]
+    )]
+    pub from_json: Option<PathBuf>,
+
     /// Custom config file path
     #[arg(short, long)]
     pub config: Option<PathBuf>,
 
+    /// Validate the config file and exit (does not require pass-cli)
+    #[arg(long)]
+    pub config_check: bool,
+
+    /// Add the options missing from an older config file, with their defaults
+    /// and comments, keeping your settings (with --dry-run, print a diff instead)
+    #[arg(long, conflicts_with_all = ["config_check", "print_config_path", "edit_config"])]
+    pub upgrade_config: bool,
+
+    /// Print the config file path (default or --config) and exit
+    #[arg(long, conflicts_with_all = ["config_check", "edit_config"])]
+    pub print_config_path: bool,
+
+    /// Open the config file in $VISUAL/$EDITOR (creating the default first) and exit
+    #[arg(long, conflicts_with = "config_check")]
+    pub edit_config: bool,
+
+    /// Print the names of the vaults matching --vault, one per line, and exit
+    #[arg(long, group = "listing", conflicts_with_all = ["purge", "from_tsh"])]
+    pub list_vaults: bool,
+
+    /// Print the titles of the items matching --vault/--item/--label, one per line, and exit
+    #[arg(long, group = "listing", conflicts_with_all = ["purge", "from_tsh"])]
+    pub list_items: bool,
+
+    /// Draw spinners and progress bars with plain ASCII characters
+    #[arg(long)]
+    pub ascii: bool,
+
+    /// Print --list-vaults/--list-items output as JSON
+    #[arg(long, requires = "listing")]
+    pub json: bool,
+
     /// Override SSH output directory (default: ~/.ssh/proton-pass)
     #[arg(short, long)]
     pub output_dir: Option<PathBuf>,
@@ -52,14 +200,67 @@ pub struct Args {
     #[arg(long, value_enum)]
     pub sync_public_key: Option<SyncPublicKey>,
 
+    /// Override how extracted key files are named
+    #[arg(long, value_enum)]
+    pub key_naming: Option<KeyNaming>,
+
+    /// Override the format private keys are written in (converted with ssh-keygen)
+    #[arg(long, value_enum)]
+    pub key_format: Option<KeyFormat>,
+
+    /// Ask before processing more than this many items (0 = unlimited)
+    #[arg(long)]
+    pub max_items: Option<usize>,
+
+    /// Override the comment template for derived public keys ({title}, {vault}, {hostname})
+    #[arg(long)]
+    pub key_comment: Option<String>,
+
     /// Override path in Proton Pass to rclone config password
     #[arg(long)]
     pub rclone_password_path: Option<String>,
 
+    /// Override the rclone config file to manage (default: ask rclone)
+    #[arg(long)]
+    pub rclone_config: Option<PathBuf>,
+
+    /// Re-tag unmanaged rclone remotes that match an item by host+user (and rename them)
+    #[arg(long, conflicts_with_all = ["ssh", "purge", "from_tsh"])]
+    pub adopt_remotes: bool,
+
+    /// Emit every directive from item Options fields, ignoring allowed_ssh_options
+    #[arg(long)]
+    pub allow_unsafe_ssh_options: bool,
+
     /// Force rclone config encryption after operations
     #[arg(long)]
     pub always_encrypt: bool,
 
+    /// Fail items whose aliases collide with another item's alias or Host (default: skip the alias)
+    #[arg(long)]
+    pub strict_duplicates: bool,
+
+    /// Never modify Proton Pass: skip public key sync-back (with a warning) and
+    /// refuse --from-tsh unless --direct-rclone
+    #[arg(long)]
+    pub read_only: bool,
+
+    /// Fail when not logged in instead of launching pass-cli login (or a tsh re-login)
+    #[arg(long)]
+    pub no_login: bool,
+
+    /// Don't warn when the SSH output directory is inside a cloud-synced folder
+    #[arg(long)]
+    pub allow_synced_dir: bool,
+
+    /// Don't warn that the config file is missing options added in newer versions
+    #[arg(long)]
+    pub no_upgrade_warning: bool,
+
+    /// Match vault and item patterns case-insensitively
+    #[arg(long)]
+    pub case_insensitive: bool,
+
     /// Import SSH entries from Teleport (tsh) login
     #[arg(long)]
     pub from_tsh: bool,
@@ -67,25 +268,172 @@ pub struct Args {
     /// Skip scanning remote servers for sftp-server path (use default)
     #[arg(long)]
     pub no_scan: bool,
+
+    /// Answer interactive confirmations with their defaults and skip the purge and
+    /// uninstall confirmations (both are irreversible); also proceeds past --max-items and
+    /// lets --full delete rclone remotes without asking; backs up and replaces an SSH config
+    /// that pass-ssh-unpack did not write
+    #[arg(short, long, visible_alias = "force")]
+    pub yes: bool,
+
+    /// Create rclone remotes for Teleport nodes directly, without Proton Pass items (with --from-tsh)
+    #[arg(long, requires = "from_tsh")]
+    pub direct_rclone: bool,
+
+    /// Teleport leaf cluster to import nodes from; adds --cluster to the stored tsh command (with --from-tsh)
+    #[arg(long, requires = "from_tsh")]
+    pub cluster: Option<String>,
+
+    /// Teleport proxy (host or host:port) for the stored tsh commands, instead of
+    /// the one `tsh status` reports (with --from-tsh)
+    #[arg(long, value_name = "ADDR", requires = "from_tsh")]
+    pub proxy: Option<String>,
+
+    /// How many nodes to scan for their sftp-server path at once (with --from-tsh)
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 8,
+        value_parser = clap::value_parser!(u64).range(1..),
+        requires = "from_tsh"
+    )]
+    pub parallel_tsh_scan: u64,
+
+    /// Use this OS family's sftp-server path instead of detecting it on each node (with --from-tsh)
+    #[arg(long, value_enum, requires = "from_tsh")]
+    pub assume_os: Option<OsFamily>,
+
+    /// Update SSH/Server Command fields of existing items when they differ (with --from-tsh)
+    #[arg(long, requires = "from_tsh")]
+    pub update_existing: bool,
+
+    /// Import each node into a vault named after its value for this Teleport label; --vault
+    /// becomes the fallback for nodes without it (with --from-tsh)
+    #[arg(
+        long,
+        value_name = "LABEL",
+        requires = "from_tsh",
+        conflicts_with = "direct_rclone"
+    )]
+    pub vault_by_label: Option<String>,
 }
 
 impl Args {
-    /// Check if the user provided any meaningful flags (non-interactive mode)
+    /// Check if the user provided any meaningful flags (non-interactive mode).
+    /// `--yes` and `--ascii` alone do not count, so they can be combined with interactive mode.
     pub fn has_flags(&self) -> bool {
         !self.vault.is_empty()
             || !self.item.is_empty()
             || self.full
+            || self.only_new
+            || self.no_write_keys
+            || self.add_to_agent
             || self.quiet
+            || self.summary_only
             || self.ssh
             || self.rclone
             || self.purge
+            || self.uninstall
+            || self.remove_config
             || self.dry_run
+            || self.diff_ssh_config
+            || self.export_rclone_config
+            || self.ansible_inventory.is_some()
+            || self.stats
+            || self.verify_ssh.is_some()
+            || self.report_file.is_some()
+            || self.fail_fast
+            || self.watch.is_some()
+            || self.json_lines
+            || self.backend != BackendKind::Proton
             || self.config.is_some()
+            || self.config_check
+            || self.print_config_path
+            || self.upgrade_config
+            || self.edit_config
+            || self.list_vaults
+            || self.list_items
             || self.output_dir.is_some()
             || self.sync_public_key.is_some()
+            || self.key_naming.is_some()
+            || !self.label.is_empty()
+            || self.label_match.is_some()
+            || self.key_format.is_some()
+            || self.key_comment.is_some()
+            || self.max_items.is_some()
             || self.rclone_password_path.is_some()
+            || self.rclone_config.is_some()
+            || self.allow_unsafe_ssh_options
+            || self.adopt_remotes
             || self.always_encrypt
+            || self.strict_duplicates
+            || self.case_insensitive
+            || self.allow_synced_dir
+            || self.no_upgrade_warning
+            || self.read_only
+            || self.no_login
             || self.from_tsh
+            || self.from_json.is_some()
             || self.no_scan
+            || self.assume_os.is_some()
+            || self.cluster.is_some()
+            || self.proxy.is_some()
+            || self.direct_rclone
+            || self.update_existing
+            || self.vault_by_label.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::error::ErrorKind;
+
+    fn parse(args: &[&str]) -> Result<Args, clap::Error> {
+        Args::try_parse_from(std::iter::once("pass-ssh-unpack").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn conflicting_flags_are_rejected() {
+        for args in [
+            &["--ssh", "--rclone"][..],
+            &["--full", "--only-new"],
+            &["--quiet", "--summary-only"],
+            &["--purge", "--watch"],
+            &["--uninstall", "--purge"],
+            &["--uninstall", "--vault", "Work"],
+            &["--from-json", "items.json", "--from-tsh"],
+            &["--from-tsh", "--label", "prod"],
+            &["--no-write-keys", "--add-to-agent"],
+            &["--json-lines", "--diff-ssh-config"],
+            &["--list-vaults", "--purge"],
+        ] {
+            let err = parse(args).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::ArgumentConflict, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn dependent_flags_need_their_parent() {
+        for args in [
+            &["--remove-config"][..],
+            &["--update-existing"],
+            &["--vault-by-label", "env"],
+        ] {
+            let err = parse(args).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn compatible_flags_parse() {
+        for args in [
+            &["--ssh", "--full"][..],
+            &["--purge", "--vault", "Work", "--label", "prod"],
+            &["--uninstall", "--remove-config", "--yes"],
+            &["--watch", "--quiet"],
+        ] {
+            assert!(parse(args).is_ok(), "{:?}", args);
+        }
     }
 }
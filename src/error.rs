@@ -1,18 +1,110 @@
-use anyhow::Error;
+use anyhow::{Error, Result};
+use std::fmt;
 
-/// Collects errors during processing to report at the end
+use crate::json_lines;
+
+/// Exit codes of the binary by failure category. Scripts rely on these, so
+/// existing values must not change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    Success = 0,
+    /// Errors were collected during the run (e.g. failed items), or a failure
+    /// with no more specific category
+    Failure = 1,
+    /// Invalid flags, flag combinations or config
+    Usage = 2,
+    /// A required tool (pass-cli, ssh-keygen, tsh) is not installed
+    MissingDependency = 3,
+    /// Not logged in to (or the session was lost with) Proton Pass or Teleport
+    Auth = 4,
+    /// The rclone config could not be decrypted, or another instance holds the lock
+    RcloneConfig = 5,
+}
+
+impl ExitCode {
+    /// An error that ends the run with this exit code
+    pub fn error(self, message: impl Into<String>) -> Error {
+        Error::new(CategorizedError {
+            code: self,
+            message: message.into(),
+        })
+    }
+
+    /// Give an existing error this exit code (its message is kept)
+    pub fn wrap(self, error: Error) -> Error {
+        self.error(format!("{:#}", error))
+    }
+
+    /// Exit code for an error: the category of its outermost categorized cause,
+    /// `Failure` if it has none
+    pub fn of(error: &Error) -> Self {
+        error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<CategorizedError>())
+            .map_or(Self::Failure, |e| e.code)
+    }
+}
+
+/// Error created by `ExitCode::error`
+#[derive(Debug)]
+struct CategorizedError {
+    code: ExitCode,
+    message: String,
+}
+
+impl fmt::Display for CategorizedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CategorizedError {}
+
+/// Collects errors (and non-fatal warnings) during processing to report at the end
 pub struct ErrorCollector {
     errors: Vec<(String, Error)>,
+    warnings: Vec<String>,
+    /// Return errors from `add` instead of collecting them (`--fail-fast`)
+    fail_fast: bool,
 }
 
 impl ErrorCollector {
     pub fn new() -> Self {
-        Self { errors: Vec::new() }
+        Self {
+            errors: Vec::new(),
+            warnings: Vec::new(),
+            fail_fast: false,
+        }
     }
 
-    /// Add an error with context
-    pub fn add(&mut self, context: &str, error: Error) {
+    /// Stop at the first error: `add` hands it back for the caller to propagate
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// Add an error with context. In fail-fast mode the error is returned
+    /// (with its context) instead, so `?` ends the run on it.
+    pub fn add(&mut self, context: &str, error: Error) -> Result<()> {
+        if self.fail_fast {
+            return Err(error.context(context.to_string()));
+        }
+        json_lines::emit(
+            "error",
+            serde_json::json!({ "context": context, "message": format!("{:#}", error) }),
+        );
         self.errors.push((context.to_string(), error));
+        Ok(())
+    }
+
+    /// Add a warning (reported, but does not fail the run)
+    pub fn add_warning(&mut self, warning: String) {
+        self.warnings.push(warning);
+    }
+
+    /// Get the collected warnings
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
     }
 
     /// Check if any errors were collected
@@ -20,17 +112,46 @@ impl ErrorCollector {
         !self.errors.is_empty()
     }
 
-    /// Report all collected errors to stderr
-    pub fn report(&self) {
+    /// Exit code for the collected errors: the first categorized one's,
+    /// otherwise `Failure` (`Success` if there are none)
+    pub fn exit_code(&self) -> ExitCode {
         if self.errors.is_empty() {
-            return;
+            return ExitCode::Success;
+        }
+        self.errors
+            .iter()
+            .map(|(_, e)| ExitCode::of(e))
+            .find(|code| *code != ExitCode::Failure)
+            .unwrap_or(ExitCode::Failure)
+    }
+
+    /// Get the collected (context, error) pairs
+    pub fn errors(&self) -> &[(String, Error)] {
+        &self.errors
+    }
+
+    /// Report all collected warnings and errors to stderr
+    pub fn report(&self) {
+        eprint!("{}", self.render());
+    }
+
+    /// The warnings and errors as `report` prints them (empty if there are none)
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        if !self.warnings.is_empty() {
+            out.push_str(&format!("\n{} warning(s):\n", self.warnings.len()));
+            for warning in &self.warnings {
+                out.push_str(&format!("  - {}\n", warning));
+            }
         }
 
-        eprintln!();
-        eprintln!("Encountered {} error(s):", self.errors.len());
-        for (context, error) in &self.errors {
-            eprintln!("  - {}: {:#}", context, error);
+        if !self.errors.is_empty() {
+            out.push_str(&format!("\nEncountered {} error(s):\n", self.errors.len()));
+            for (context, error) in &self.errors {
+                out.push_str(&format!("  - {}: {:#}\n", context, error));
+            }
         }
+        out
     }
 }
 
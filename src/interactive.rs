@@ -1,11 +1,14 @@
 use anyhow::Result;
 use inquire::{Confirm, MultiSelect, Select, Text};
 use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use crate::config::{Config, DEFAULT_RCLONE_PASSWORD_PATH};
+use crate::backend::SecretBackend;
+use crate::config::Config;
 use crate::progress;
 use crate::proton_pass::ProtonPass;
 use crate::teleport::Teleport;
+use crate::tools;
 
 /// Result of interactive mode - what action to take
 pub enum InteractiveAction {
@@ -14,6 +17,7 @@ pub enum InteractiveAction {
         vault: String,
         item_pattern: Option<String>,
         scan_remotes: bool,
+        cluster: Option<String>,
         dry_run: bool,
     },
     /// Export to local machine
@@ -71,8 +75,24 @@ pub fn is_interactive() -> bool {
     std::io::stdin().is_terminal() && std::io::stdout().is_terminal()
 }
 
-/// Run interactive mode and return the chosen action
-pub fn run_interactive() -> Result<InteractiveAction> {
+/// Cleared by `auto_login = false` / `--no-login`
+static AUTO_LOGIN: AtomicBool = AtomicBool::new(true);
+
+/// Allow or forbid launching interactive logins (pass-cli login, tsh re-login)
+pub fn set_auto_login(enabled: bool) {
+    AUTO_LOGIN.store(enabled, Ordering::SeqCst);
+}
+
+/// Whether a login may be launched: auto login is on and stdin is a terminal
+/// someone can type into (never in CI)
+pub fn can_login() -> bool {
+    AUTO_LOGIN.load(Ordering::SeqCst) && std::io::stdin().is_terminal()
+}
+
+/// Run interactive mode and return the chosen action.
+/// With `assume_yes`, confirmations are answered with their defaults and the
+/// typed purge confirmation is skipped.
+pub fn run_interactive(assume_yes: bool) -> Result<InteractiveAction> {
     println!();
     println!("  pass-ssh-unpack");
     println!("  ───────────────");
@@ -98,16 +118,16 @@ pub fn run_interactive() -> Result<InteractiveAction> {
     };
 
     match action {
-        "Export Proton Pass SSH to local machine" => run_export_local(),
-        "Import Teleport nodes into Proton Pass" => run_teleport_import(),
+        "Export Proton Pass SSH to local machine" => run_export_local(assume_yes),
+        "Import Teleport nodes into Proton Pass" => run_teleport_import(assume_yes),
         "View status" => run_view_status(),
-        "Purge managed resources" => run_purge(),
+        "Purge managed resources" => run_purge(assume_yes),
         "Quit" => Ok(InteractiveAction::Cancelled),
         _ => Ok(InteractiveAction::Cancelled),
     }
 }
 
-fn run_teleport_import() -> Result<InteractiveAction> {
+fn run_teleport_import(assume_yes: bool) -> Result<InteractiveAction> {
     println!();
 
     // Check if tsh is installed
@@ -122,14 +142,38 @@ fn run_teleport_import() -> Result<InteractiveAction> {
     let status = teleport.get_status();
     spinner.finish_and_clear();
 
-    if status.is_err() {
+    let Ok(status) = status else {
         println!("Not logged into Teleport. Run 'tsh login' first.");
         return Ok(InteractiveAction::Cancelled);
-    }
+    };
+
+    // Let the user pick a cluster when leaf clusters are available
+    let clusters = teleport.list_clusters().unwrap_or_default();
+    let cluster = if clusters.len() > 1 {
+        match Select::new("Select Teleport cluster:", clusters)
+            .with_help_message("Nodes are imported from this cluster.")
+            .prompt()
+        {
+            Ok(c) if c == status.cluster => None,
+            Ok(c) => Some(c),
+            Err(
+                inquire::InquireError::OperationCanceled
+                | inquire::InquireError::OperationInterrupted,
+            ) => {
+                return Ok(InteractiveAction::Cancelled);
+            }
+            Err(e) => return Err(e.into()),
+        }
+    } else {
+        None
+    };
 
     // Fetch available vaults
     let proton_pass = ProtonPass::new();
-    let available_vaults = proton_pass.list_vaults().unwrap_or_default();
+    let skip_vaults = Config::load_or_create(&None)
+        .unwrap_or_default()
+        .skip_vaults;
+    let available_vaults = proton_pass.list_vaults(&skip_vaults).unwrap_or_default();
 
     // Ask for vault selection
     let vault = if available_vaults.is_empty() {
@@ -211,11 +255,12 @@ fn run_teleport_import() -> Result<InteractiveAction> {
     };
 
     // Ask about scanning
-    let scan_remotes = match Confirm::new("Scan each server to detect sftp-server path?")
-        .with_default(true)
-        .with_help_message("Slower but more accurate. Skip to use default path.")
-        .prompt()
-    {
+    let scan_remotes = match ask_confirm(
+        Confirm::new("Scan each server to detect sftp-server path?")
+            .with_default(true)
+            .with_help_message("Slower but more accurate. Skip to use default path."),
+        assume_yes,
+    ) {
         Ok(v) => v,
         Err(
             inquire::InquireError::OperationCanceled | inquire::InquireError::OperationInterrupted,
@@ -226,7 +271,7 @@ fn run_teleport_import() -> Result<InteractiveAction> {
     };
 
     // Ask about dry run
-    let dry_run = ask_dry_run()?;
+    let dry_run = ask_dry_run(assume_yes)?;
     if dry_run.is_none() {
         return Ok(InteractiveAction::Cancelled);
     }
@@ -240,13 +285,14 @@ fn run_teleport_import() -> Result<InteractiveAction> {
     let summary = [
         "Action:  Import Teleport nodes".to_string(),
         format!("Vault:   {}", vault),
+        format!("Cluster: {}", cluster.as_deref().unwrap_or(&status.cluster)),
         format!("Nodes:   {}", nodes_str),
         format!("Scan:    {}", scan_str),
         format!("Dry run: {}", dry_run_str),
     ];
     let summary_refs: Vec<&str> = summary.iter().map(|s| s.as_str()).collect();
 
-    if !confirm_summary(&summary_refs)? {
+    if !confirm_summary(&summary_refs, assume_yes)? {
         return Ok(InteractiveAction::Cancelled);
     }
 
@@ -254,11 +300,12 @@ fn run_teleport_import() -> Result<InteractiveAction> {
         vault,
         item_pattern,
         scan_remotes,
+        cluster,
         dry_run,
     })
 }
 
-fn run_export_local() -> Result<InteractiveAction> {
+fn run_export_local(assume_yes: bool) -> Result<InteractiveAction> {
     println!();
 
     // Ask what to export
@@ -280,7 +327,10 @@ fn run_export_local() -> Result<InteractiveAction> {
 
     // Fetch available vaults
     let proton_pass = ProtonPass::new();
-    let available_vaults = proton_pass.list_vaults().unwrap_or_default();
+    let skip_vaults = Config::load_or_create(&None)
+        .unwrap_or_default()
+        .skip_vaults;
+    let available_vaults = proton_pass.list_vaults(&skip_vaults).unwrap_or_default();
 
     // Ask for vault selection (multi-select if vaults available, fall back to text)
     let vaults = if available_vaults.is_empty() {
@@ -333,11 +383,12 @@ fn run_export_local() -> Result<InteractiveAction> {
     };
 
     // Ask about full regeneration
-    let full = match Confirm::new("Full regeneration? (clear existing config first)")
-        .with_default(false)
-        .with_help_message("Use this to remove stale entries from previous runs.")
-        .prompt()
-    {
+    let full = match ask_confirm(
+        Confirm::new("Full regeneration? (clear existing config first)")
+            .with_default(false)
+            .with_help_message("Use this to remove stale entries from previous runs."),
+        assume_yes,
+    ) {
         Ok(v) => v,
         Err(
             inquire::InquireError::OperationCanceled | inquire::InquireError::OperationInterrupted,
@@ -348,7 +399,7 @@ fn run_export_local() -> Result<InteractiveAction> {
     };
 
     // Ask about dry run
-    let dry_run = ask_dry_run()?;
+    let dry_run = ask_dry_run(assume_yes)?;
     if dry_run.is_none() {
         return Ok(InteractiveAction::Cancelled);
     }
@@ -375,7 +426,7 @@ fn run_export_local() -> Result<InteractiveAction> {
     ];
     let summary_refs: Vec<&str> = summary.iter().map(|s| s.as_str()).collect();
 
-    if !confirm_summary(&summary_refs)? {
+    if !confirm_summary(&summary_refs, assume_yes)? {
         return Ok(InteractiveAction::Cancelled);
     }
 
@@ -388,12 +439,26 @@ fn run_export_local() -> Result<InteractiveAction> {
     })
 }
 
-fn ask_dry_run() -> Result<Option<bool>> {
+/// Prompt a confirmation, or take its default without prompting when `assume_yes` is set
+fn ask_confirm(confirm: Confirm, assume_yes: bool) -> inquire::error::InquireResult<bool> {
+    if assume_yes {
+        let answer = confirm.default.unwrap_or(true);
+        println!(
+            "{} {} (--yes)",
+            confirm.message,
+            if answer { "Yes" } else { "No" }
+        );
+        return Ok(answer);
+    }
+    confirm.prompt()
+}
+
+fn ask_dry_run(assume_yes: bool) -> Result<Option<bool>> {
     println!();
-    match Confirm::new("Dry run? (preview changes without applying)")
-        .with_default(false)
-        .prompt()
-    {
+    match ask_confirm(
+        Confirm::new("Dry run? (preview changes without applying)").with_default(false),
+        assume_yes,
+    ) {
         Ok(v) => Ok(Some(v)),
         Err(
             inquire::InquireError::OperationCanceled | inquire::InquireError::OperationInterrupted,
@@ -403,7 +468,7 @@ fn ask_dry_run() -> Result<Option<bool>> {
 }
 
 /// Show a summary and ask for final confirmation
-fn confirm_summary(lines: &[&str]) -> Result<bool> {
+fn confirm_summary(lines: &[&str], assume_yes: bool) -> Result<bool> {
     println!();
     println!("  Summary");
     println!("  ───────");
@@ -412,7 +477,7 @@ fn confirm_summary(lines: &[&str]) -> Result<bool> {
     }
     println!();
 
-    match Confirm::new("Proceed?").with_default(true).prompt() {
+    match ask_confirm(Confirm::new("Proceed?").with_default(true), assume_yes) {
         Ok(v) => Ok(v),
         Err(
             inquire::InquireError::OperationCanceled | inquire::InquireError::OperationInterrupted,
@@ -421,7 +486,7 @@ fn confirm_summary(lines: &[&str]) -> Result<bool> {
     }
 }
 
-fn run_purge() -> Result<InteractiveAction> {
+fn run_purge(assume_yes: bool) -> Result<InteractiveAction> {
     println!();
 
     // Ask what to purge
@@ -438,7 +503,7 @@ fn run_purge() -> Result<InteractiveAction> {
     };
 
     // Ask about dry run first
-    let dry_run = ask_dry_run()?;
+    let dry_run = ask_dry_run(assume_yes)?;
     if dry_run.is_none() {
         return Ok(InteractiveAction::Cancelled);
     }
@@ -455,7 +520,7 @@ fn run_purge() -> Result<InteractiveAction> {
     ];
     let summary_refs: Vec<&str> = summary.iter().map(|s| s.as_str()).collect();
 
-    if !confirm_summary(&summary_refs)? {
+    if !confirm_summary(&summary_refs, assume_yes)? {
         return Ok(InteractiveAction::Cancelled);
     }
 
@@ -475,6 +540,11 @@ fn run_purge() -> Result<InteractiveAction> {
         println!("  Proton Pass will NOT be modified.");
         println!();
 
+        if assume_yes {
+            println!("  Skipping purge confirmation (--yes).");
+            return Ok(InteractiveAction::Purge { mode, dry_run });
+        }
+
         let confirmation = match Text::new("Type 'purge' to confirm:")
             .with_help_message("This action cannot be undone.")
             .prompt()
@@ -509,9 +579,24 @@ fn run_view_status() -> Result<InteractiveAction> {
     println!("  Version:         v{}", VERSION);
     println!();
 
+    // Show external tool versions against the known-good minimums
+    println!("  Tools:");
+    for tool in tools::TOOLS {
+        let detected = match tool.version() {
+            Some(version) if version < tool.minimum => {
+                format!("{} (older than {} - please upgrade)", version, tool.minimum)
+            }
+            Some(version) => version.to_string(),
+            None if which::which(tool.name).is_ok() => "(unknown version)".to_string(),
+            None => "(not installed)".to_string(),
+        };
+        println!("    {:<13}{}", format!("{}:", tool.name), detected);
+    }
+    println!();
+
     // Load config
     let config = Config::load_or_create(&None).unwrap_or_default();
-    let ssh_dir = config.expanded_ssh_output_dir();
+    let ssh_dir = config.expanded_ssh_output_dir()?;
     let config_path = Config::default_path();
 
     // Count SSH keys
@@ -523,8 +608,11 @@ fn run_view_status() -> Result<InteractiveAction> {
                     .filter(|e| {
                         let name = e.file_name();
                         let name_str = name.to_string_lossy();
-                        // Count files that look like private keys (no extension, not config)
-                        !name_str.contains('.') && name_str != "config"
+                        // Count files that look like private keys (no extension, not
+                        // config); directories such as the multiplexing sockets are skipped
+                        e.file_type().is_ok_and(|t| t.is_file())
+                            && !name_str.contains('.')
+                            && name_str != "config"
                     })
                     .count()
             })
@@ -544,33 +632,25 @@ fn run_view_status() -> Result<InteractiveAction> {
     };
 
     // Count rclone remotes (managed by us)
-    // First, try to load rclone password if configured (or check if already in env)
-    let mut rclone_password_available = std::env::var("RCLONE_CONFIG_PASS").is_ok();
-    if !rclone_password_available {
-        // Use configured password_path, or fall back to default
-        let password_path = if config.rclone.password_path.is_empty() {
-            DEFAULT_RCLONE_PASSWORD_PATH
-        } else {
-            &config.rclone.password_path
-        };
-
+    // First, try to load the password for the rclone config file
+    let rclone_config_path = crate::rclone::get_config_path(&config).ok();
+    let rclone_password = rclone_config_path.as_ref().and_then(|path| {
         let spinner = progress::spinner("Loading rclone password...");
-        let proton_pass = ProtonPass::new();
-        if let Ok(password) = proton_pass.get_item_field(password_path) {
-            std::env::set_var("RCLONE_CONFIG_PASS", password);
-            rclone_password_available = true;
-        }
+        let password = crate::rclone::load_password(&config, path).ok();
         spinner.finish_and_clear();
-    }
+        password
+    });
 
     // Count remotes (this decrypts the config internally via rclone)
     let spinner = progress::spinner("Decrypting rclone config...");
-    let rclone_count = count_managed_rclone_remotes();
+    let rclone_count = rclone_config_path
+        .as_ref()
+        .and_then(|path| crate::rclone::count_managed_remotes(path, rclone_password.as_deref()));
     spinner.finish_and_clear();
     let rclone_str = match rclone_count {
         Some(count) => count.to_string(),
         None => {
-            if !rclone_password_available {
+            if rclone_password.is_none() {
                 "(encrypted)".to_string()
             } else {
                 "(encrypted - wrong password?)".to_string()
@@ -591,41 +671,3 @@ fn run_view_status() -> Result<InteractiveAction> {
     // Return to menu after showing status
     Ok(InteractiveAction::ViewedStatus)
 }
-
-/// Count rclone remotes managed by pass-ssh-unpack
-/// Returns None if config is encrypted and can't be read
-fn count_managed_rclone_remotes() -> Option<usize> {
-    // Use rclone config dump which outputs JSON (works with RCLONE_CONFIG_PASS env var)
-    let output = std::process::Command::new("rclone")
-        .args(["config", "dump"])
-        .env("RCLONE_ASK_PASSWORD", "false")
-        .output()
-        .ok()?;
-
-    if !output.status.success() {
-        // Likely encrypted and no password available
-        return None;
-    }
-
-    if output.stdout.is_empty() {
-        return Some(0);
-    }
-
-    // Parse JSON output - it's a map of remote_name -> remote_config
-    let config: std::collections::HashMap<String, serde_json::Value> =
-        serde_json::from_slice(&output.stdout).ok()?;
-
-    // Count remotes with description = "managed by pass-ssh-unpack"
-    let count = config
-        .values()
-        .filter(|remote| {
-            remote
-                .get("description")
-                .and_then(|d| d.as_str())
-                .map(|d| d == "managed by pass-ssh-unpack")
-                .unwrap_or(false)
-        })
-        .count();
-
-    Some(count)
-}
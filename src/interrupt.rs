@@ -0,0 +1,80 @@
+use anyhow::{Context, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set when Ctrl-C arrives while a critical section is active
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Set while rclone config changes are in flight
+static CRITICAL: AtomicBool = AtomicBool::new(false);
+
+/// Set while the vault/item loops run, which stop early instead of exiting
+static CANCELLABLE: AtomicBool = AtomicBool::new(false);
+
+/// Install the Ctrl-C handler.
+///
+/// Outside a critical or cancellable section Ctrl-C exits immediately, as
+/// before. Inside a critical section (the rclone phase) the first Ctrl-C only
+/// sets a flag so the current step can finish or roll back cleanly; inside a
+/// cancellable one (the item loops) it stops the loops after the current item
+/// so the results so far are still written. A second Ctrl-C forces an exit.
+pub fn install_handler() -> Result<()> {
+    ctrlc::set_handler(|| {
+        let critical = CRITICAL.load(Ordering::SeqCst);
+        let cancellable = CANCELLABLE.load(Ordering::SeqCst);
+        if (critical || cancellable) && !INTERRUPTED.swap(true, Ordering::SeqCst) {
+            eprintln!();
+            if critical {
+                eprintln!(
+                    "Interrupted - finishing the current rclone step (Ctrl-C again to force quit)..."
+                );
+            } else {
+                eprintln!(
+                    "Interrupted - stopping after the current item (Ctrl-C again to force quit)..."
+                );
+            }
+            return;
+        }
+
+        std::process::exit(130);
+    })
+    .context("Failed to install Ctrl-C handler")
+}
+
+/// Check if Ctrl-C was pressed during a critical or cancellable section
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Marks a critical section for the Ctrl-C handler until dropped
+pub struct CriticalSection;
+
+impl CriticalSection {
+    pub fn enter() -> Self {
+        INTERRUPTED.store(false, Ordering::SeqCst);
+        CRITICAL.store(true, Ordering::SeqCst);
+        Self
+    }
+}
+
+impl Drop for CriticalSection {
+    fn drop(&mut self) {
+        CRITICAL.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Marks loops that check `is_interrupted` between iterations, until dropped
+pub struct CancellableSection;
+
+impl CancellableSection {
+    pub fn enter() -> Self {
+        INTERRUPTED.store(false, Ordering::SeqCst);
+        CANCELLABLE.store(true, Ordering::SeqCst);
+        Self
+    }
+}
+
+impl Drop for CancellableSection {
+    fn drop(&mut self) {
+        CANCELLABLE.store(false, Ordering::SeqCst);
+    }
+}
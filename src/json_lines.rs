@@ -0,0 +1,71 @@
+use serde_json::{Map, Value};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Set by `--json-lines`; records are only written while enabled
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turn on JSON Lines output for the rest of the process
+pub fn enable() {
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+/// Check if JSON Lines output is on
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// Write one record to stdout as a single line, tagged with its `type` and a
+/// UTC `timestamp`. Does nothing unless output was enabled.
+pub fn emit(kind: &str, fields: Value) {
+    if !is_enabled() {
+        return;
+    }
+
+    let mut record = Map::new();
+    record.insert("type".to_string(), Value::from(kind));
+    record.insert("timestamp".to_string(), Value::from(timestamp()));
+    if let Value::Object(fields) = fields {
+        record.extend(fields);
+    }
+
+    // Flush per record so consumers can tail the stream as it happens
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{}", Value::Object(record));
+    let _ = stdout.flush();
+}
+
+/// Current time as RFC 3339 UTC with milliseconds (e.g. 2024-05-01T12:34:56.789Z)
+pub fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let rem = secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        now.subsec_millis()
+    )
+}
+
+/// Convert days since 1970-01-01 to a (year, month, day) date
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,47 @@
+//! Extract SSH keys from Proton Pass to local files and generate SSH and rclone
+//! configurations.
+//!
+//! The `pass-ssh-unpack` binary is a thin wrapper around [`runner`]; the same
+//! workflows can be driven from other programs:
+//!
+//! ```no_run
+//! use pass_ssh_unpack::{run_export, Config, ExportOptions};
+//!
+//! let config = Config::load_or_create(&None)?;
+//! let options = ExportOptions {
+//!     vaults: vec!["Personal".to_string()],
+//!     quiet: true,
+//!     ..Default::default()
+//! };
+//! let report = run_export(&config, &options)?;
+//! println!("{} hosts, {} aliases", report.hosts, report.aliases);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub mod ansible;
+pub mod backend;
+pub mod cli;
+pub mod config;
+pub mod error;
+pub mod interactive;
+pub mod interrupt;
+pub mod json_lines;
+pub mod lock;
+pub mod platform;
+pub mod progress;
+pub mod proton_pass;
+pub mod rclone;
+pub mod runner;
+pub mod ssh;
+pub mod stats;
+pub mod teleport;
+pub mod tools;
+
+pub use backend::{BackendKind, SecretBackend};
+pub use config::Config;
+pub use error::{ErrorCollector, ExitCode};
+pub use rclone::RcloneEntry;
+pub use runner::{
+    import_from_tsh, rclone_from_tsh, run_export, ExportOptions, RunReport, TshImportOptions,
+    TshImportReport,
+};
@@ -0,0 +1,48 @@
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::error::ExitCode;
+
+/// Advisory lock held while a run mutates local state (SSH config, rclone config).
+/// Released when dropped, which also happens while unwinding from a panic;
+/// the OS releases it if the process dies.
+pub struct InstanceLock {
+    _file: File,
+}
+
+impl InstanceLock {
+    /// Path of the lock file (next to the default config file)
+    pub fn path() -> PathBuf {
+        Config::default_path().with_file_name("pass-ssh-unpack.lock")
+    }
+
+    /// Acquire the lock, failing fast if another instance holds it
+    pub fn acquire() -> Result<Self> {
+        let path = Self::path();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open lock file {}", path.display()))?;
+
+        match file.try_lock() {
+            Ok(()) => Ok(Self { _file: file }),
+            Err(TryLockError::WouldBlock) => Err(ExitCode::RcloneConfig.error(format!(
+                "Another pass-ssh-unpack is running (lock held on {})",
+                path.display()
+            ))),
+            Err(TryLockError::Error(e)) => {
+                Err(e).with_context(|| format!("Failed to lock {}", path.display()))
+            }
+        }
+    }
+}
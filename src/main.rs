@@ -1,41 +1,38 @@
-mod cli;
-mod config;
-mod error;
-mod interactive;
-mod platform;
-mod progress;
-mod proton_pass;
-mod rclone;
-mod ssh;
-mod teleport;
-
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Parser;
-use std::collections::HashSet;
-
-use cli::Args;
-use config::Config;
-use error::ErrorCollector;
-use interactive::{ExportMode, InteractiveAction, PurgeMode};
-use proton_pass::ProtonPass;
-use rclone::RcloneEntry;
-use ssh::SshManager;
-use teleport::Teleport;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use pass_ssh_unpack::cli::Args;
+use pass_ssh_unpack::config::{self, Config, SyncPublicKey, Theme};
+use pass_ssh_unpack::error::ExitCode;
+use pass_ssh_unpack::interactive::{self, ExportMode, InteractiveAction, PurgeMode};
+use pass_ssh_unpack::interrupt;
+use pass_ssh_unpack::json_lines;
+use pass_ssh_unpack::platform;
+use pass_ssh_unpack::progress;
+use pass_ssh_unpack::runner::{self, ExportOptions, TshImportOptions, UninstallOptions};
 
 fn main() {
     if let Err(e) = run() {
         eprintln!("Error: {:#}", e);
-        std::process::exit(1);
+        std::process::exit(ExitCode::of(&e) as i32);
     }
 }
 
 fn run() -> Result<()> {
     let args = Args::parse();
 
+    interrupt::install_handler()?;
+    if args.ascii {
+        progress::set_theme(Theme::Ascii, true);
+    }
+
     // If no flags provided, try interactive mode
     if !args.has_flags() {
         if interactive::is_interactive() {
-            return run_interactive_mode();
+            return run_interactive_mode(args.yes, args.ascii);
         } else {
             // Not a TTY - show help instead
             eprintln!("No arguments provided and not running in an interactive terminal.");
@@ -52,6 +49,34 @@ fn run() -> Result<()> {
         }
     }
 
+    // Handle --print-config-path / --edit-config (before anything touches pass-cli)
+    if args.print_config_path {
+        let config_path = args.config.clone().unwrap_or_else(Config::default_path);
+        println!("{}", config_path.display());
+        return Ok(());
+    }
+    if args.edit_config {
+        return handle_edit_config(&args);
+    }
+    if args.upgrade_config {
+        return handle_upgrade_config(&args);
+    }
+
+    // Handle --config-check (validate and exit)
+    if args.config_check {
+        return handle_config_check(&args);
+    }
+
+    // Handle --list-vaults / --list-items (print and exit)
+    if args.list_vaults || args.list_items {
+        return handle_list(&args);
+    }
+
+    // Handle --uninstall (remove everything and exit)
+    if args.uninstall {
+        return handle_uninstall(&args);
+    }
+
     // Handle --from-tsh mode (separate workflow)
     if args.from_tsh {
         return handle_from_tsh(&args);
@@ -61,575 +86,486 @@ fn run() -> Result<()> {
     run_export(&args)
 }
 
-fn run_export(args: &Args) -> Result<()> {
-    let mut errors = ErrorCollector::new();
-    let dry_run = args.dry_run;
-
-    // Load or create config
+fn handle_edit_config(args: &Args) -> Result<()> {
     let config_path = args.config.clone().unwrap_or_else(Config::default_path);
-    let mut config = Config::load_or_create(&args.config)?;
-
-    // Apply CLI overrides to config
-    if let Some(ref output_dir) = args.output_dir {
-        config.ssh_output_dir = output_dir.to_string_lossy().to_string();
-    }
-    if let Some(sync_public_key) = args.sync_public_key {
-        config.sync_public_key = sync_public_key;
-    }
-    if let Some(ref password_path) = args.rclone_password_path {
-        config.rclone.password_path = password_path.clone();
-    }
-    if args.always_encrypt {
-        config.rclone.always_encrypt = true;
+    if !config_path.exists() {
+        Config::create_default(&config_path)?;
+        println!("Created default config: {}", config_path.display());
     }
 
-    // Determine which operations to run
-    // --ssh: only SSH, --rclone: only rclone, neither: both
-    let do_ssh = !args.rclone; // SSH unless --rclone only
-    let do_rclone = !args.ssh && config.rclone.enabled; // rclone unless --ssh only
+    let editor = platform::editor_command();
+    let status = std::process::Command::new(&editor[0])
+        .args(&editor[1..])
+        .arg(&config_path)
+        .status()
+        .with_context(|| format!("Failed to start editor \"{}\"", editor.join(" ")))?;
+    if !status.success() {
+        anyhow::bail!("Editor \"{}\" exited with {}", editor.join(" "), status);
+    }
 
-    // Helper for logging
-    let log = |msg: &str| {
-        if !args.quiet {
-            println!("{}", msg);
-        }
-    };
+    println!("Run pass-ssh-unpack --config-check to validate your changes.");
+    Ok(())
+}
 
-    // Check for missing config options and warn user
-    if config_path.exists() {
-        let missing = config::check_missing_options(&config_path);
-        if !missing.is_empty() && !args.quiet {
-            eprintln!(
-                "Warning: Your config is missing new options: {}",
-                missing.join(", ")
-            );
-            eprintln!(
-                "  Consider regenerating with: rm {:?} && pass-ssh-unpack",
-                config_path
-            );
-            eprintln!();
+fn handle_upgrade_config(args: &Args) -> Result<()> {
+    let config_path = args.config.clone().unwrap_or_else(Config::default_path);
+    if !config_path.exists() {
+        if args.dry_run {
+            println!("Would create default config: {}", config_path.display());
+        } else {
+            Config::create_default(&config_path)?;
+            println!("Created default config: {}", config_path.display());
         }
+        return Ok(());
     }
 
-    if dry_run {
-        log("[DRY RUN] No changes will be made");
-        log("");
-    }
-
-    // Check dependencies
-    check_dependencies()?;
+    let content = std::fs::read_to_string(&config_path)
+        .with_context(|| format!("Failed to read config file: {}", config_path.display()))?;
+    let Some((upgraded, added)) = config::upgraded_config(&content)
+        .with_context(|| format!("Cannot upgrade {}", config_path.display()))?
+    else {
+        println!("Config is up to date: {}", config_path.display());
+        return Ok(());
+    };
 
-    // Handle purge mode
-    if args.purge {
-        return handle_purge(&config, dry_run, args.quiet, do_ssh, do_rclone);
+    if args.dry_run {
+        let path = config_path.display().to_string();
+        print!(
+            "{}",
+            similar::TextDiff::from_lines(&content, &upgraded)
+                .unified_diff()
+                .header(&path, &format!("{} (upgraded)", path))
+        );
+        println!(
+            "[DRY RUN] Would add {} option(s): {}",
+            added.len(),
+            added.join(", ")
+        );
+        return Ok(());
     }
 
-    if do_ssh {
-        log("Extracting SSH keys from Proton Pass...");
-    } else {
-        log("Syncing rclone remotes only...");
-    }
-    log("");
+    std::fs::write(&config_path, upgraded)
+        .with_context(|| format!("Failed to write config file: {}", config_path.display()))?;
+    println!(
+        "Added {} option(s) to {}: {}",
+        added.len(),
+        config_path.display(),
+        added.join(", ")
+    );
+    Ok(())
+}
 
-    // Get current hostname for machine-specific filtering
-    let current_hostname = platform::get_hostname();
+fn handle_config_check(args: &Args) -> Result<()> {
+    let config_path = args.config.clone().unwrap_or_else(Config::default_path);
 
-    // Setup SSH manager
-    let ssh_output_dir = config.expanded_ssh_output_dir();
-    let mut ssh_manager =
-        SshManager::new(&ssh_output_dir, args.full, dry_run, config.sync_public_key)?;
+    println!("Checking config: {}", config_path.display());
 
-    // Get vaults to process
-    let proton_pass = ProtonPass::new();
-    let spinner = if !args.quiet {
-        Some(progress::spinner("Loading vaults..."))
-    } else {
-        None
-    };
-    let all_vaults = proton_pass.list_vaults()?;
-    if let Some(sp) = spinner {
-        sp.finish_and_clear();
+    if !config_path.exists() {
+        println!("  Config file does not exist (defaults will be used).");
+        return Ok(());
     }
 
-    // Apply vault filters (CLI overrides config defaults)
-    let vault_patterns = if args.vault.is_empty() {
-        &config.default_vaults
-    } else {
-        &args.vault
-    };
-
-    let vaults_to_process = filter_by_patterns(&all_vaults, vault_patterns);
+    let report = config::check_config(&config_path);
 
-    if vaults_to_process.is_empty() && !vault_patterns.is_empty() {
-        log("Warning: No vaults matched the specified patterns");
+    for error in &report.errors {
+        println!("  error: {}", error);
+    }
+    for warning in &report.warnings {
+        println!("  warning: {}", warning);
     }
 
-    // Apply item filters (CLI overrides config defaults)
-    let item_patterns = if args.item.is_empty() {
-        &config.default_items
+    if report.is_ok() {
+        println!("Config OK ({} warning(s)).", report.warnings.len());
+        Ok(())
     } else {
-        &args.item
-    };
-
-    // Collect rclone entries for later sync
-    let mut rclone_entries: Vec<RcloneEntry> = Vec::new();
+        println!(
+            "Config check failed: {} error(s), {} warning(s).",
+            report.errors.len(),
+            report.warnings.len()
+        );
+        std::process::exit(ExitCode::Usage as i32);
+    }
+}
 
-    // Process each vault with progress bar (if doing SSH or rclone)
-    if do_ssh || do_rclone {
-        let vault_pb = if !args.quiet && !vaults_to_process.is_empty() {
-            Some(progress::vault_progress_bar(vaults_to_process.len() as u64))
+fn handle_list(args: &Args) -> Result<()> {
+    let config = load_config(args)?;
+
+    if args.list_vaults {
+        let vaults = runner::list_vaults(
+            &config,
+            args.backend,
+            args.from_json.as_deref(),
+            &args.vault,
+        )?;
+        if args.json {
+            println!("{}", serde_json::to_string_pretty(&vaults)?);
         } else {
-            None
-        };
-
-        // Helper for logging that works with progress bar
-        let pb_log = |msg: &str| {
-            if !args.quiet {
-                if let Some(ref pb) = vault_pb {
-                    pb.println(msg);
-                } else {
-                    println!("{}", msg);
-                }
-            }
-        };
-
-        for (i, vault) in vaults_to_process.iter().enumerate() {
-            pb_log(&format!("[{}]", vault));
-
-            let items = match proton_pass.list_all_items(vault) {
-                Ok(items) => items,
-                Err(e) => {
-                    errors.add(&format!("Failed to list items in vault '{}'", vault), e);
-                    pb_log("  (error listing items)");
-                    pb_log("");
-                    if let Some(ref pb) = vault_pb {
-                        pb.set_position(i as u64 + 1);
-                    }
-                    continue;
-                }
-            };
-
-            if items.is_empty() {
-                pb_log("  (no items)");
-                pb_log("");
-                if let Some(ref pb) = vault_pb {
-                    pb.set_position(i as u64 + 1);
-                }
-                continue;
-            }
-
-            for item in items {
-                // Filter by item patterns
-                if !matches_any_pattern(&item.title, item_patterns) {
-                    continue;
-                }
-
-                // Skip Teleport-only items (no host, has ssh command) when not doing rclone
-                let is_teleport_only = item.host.is_none() && item.ssh.is_some();
-                if is_teleport_only && !do_rclone {
-                    continue;
-                }
-
-                // Check machine-specific suffix
-                if let Some(suffix) = item.title.split('/').next_back() {
-                    if item.title.contains('/') {
-                        let suffix_lower = suffix.to_lowercase();
-                        if suffix_lower != current_hostname.to_lowercase() {
-                            pb_log(&format!(
-                                "  Skipping: {} (not for this machine)",
-                                item.title
-                            ));
-                            continue;
-                        }
-                    }
-                }
-
-                pb_log(&format!("  Processing: {}", item.title));
-
-                // Extract and process the SSH key
-                match ssh_manager.process_item(&proton_pass, vault, &item, &pb_log) {
-                    Ok(entry) => {
-                        if let Some(rclone_entry) = entry {
-                            rclone_entries.push(rclone_entry);
-                        }
-                    }
-                    Err(e) => {
-                        errors.add(&format!("Failed to process '{}'", item.title), e);
-                    }
-                }
-            }
-
-            pb_log("");
-            if let Some(ref pb) = vault_pb {
-                pb.set_position(i as u64 + 1);
+            for vault in &vaults {
+                println!("{}", vault);
             }
         }
-
-        if let Some(pb) = vault_pb {
-            pb.finish_and_clear();
-        }
-
-        // Generate SSH config (only if doing SSH)
-        if do_ssh {
-            log("Generating SSH config...");
-            let (primary_count, alias_count) = ssh_manager.write_config()?;
-
-            log("");
-            log(&format!(
-                "Done! Generated config has {} hosts and {} aliases.",
-                primary_count, alias_count
-            ));
-            log(&format!(
-                "SSH config written to: {}",
-                ssh_manager.config_path().display()
-            ));
-        }
-    }
-
-    // Sync rclone remotes
-    if do_rclone {
-        if let Err(e) =
-            rclone::sync_remotes(&rclone_entries, &config, args.full, dry_run, args.quiet)
-        {
-            errors.add("Rclone sync", e);
+    } else {
+        let items = runner::list_items(
+            &config,
+            args.backend,
+            args.from_json.as_deref(),
+            &args.vault,
+            &args.item,
+            &args.label,
+        )?;
+        if args.json {
+            let items: Vec<_> = items
+                .iter()
+                .map(|(vault, title)| serde_json::json!({ "vault": vault, "title": title }))
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&items)?);
+        } else {
+            for (_, title) in &items {
+                println!("{}", title);
+            }
         }
     }
-
-    // Report any collected errors
-    errors.report();
-
-    if errors.has_errors() {
-        std::process::exit(1);
-    }
-
     Ok(())
 }
 
-fn check_dependencies() -> Result<()> {
-    use anyhow::bail;
-
-    if which::which("pass-cli").is_err() {
-        bail!("pass-cli not found. Install Proton Pass CLI first.");
-    }
-
-    // Check if logged in (with spinner since this can be slow)
-    let spinner = progress::spinner("Checking Proton Pass login...");
-    let output = std::process::Command::new("pass-cli")
-        .arg("info")
-        .output()?;
-    spinner.finish_and_clear();
-
-    if !output.status.success() {
-        eprintln!("Not logged into Proton Pass. Launching login...");
-        eprintln!();
-
-        // Try to login interactively
-        let login_status = std::process::Command::new("pass-cli")
-            .arg("login")
-            .stdin(std::process::Stdio::inherit())
-            .stdout(std::process::Stdio::inherit())
-            .stderr(std::process::Stdio::inherit())
-            .status()?;
+/// Load (or create) the config file and apply CLI overrides
+fn load_config(args: &Args) -> Result<Config> {
+    Config::load_or_create(&args.config)
+        .and_then(|config| apply_overrides(config, args))
+        .map_err(|e| ExitCode::Usage.wrap(e))
+}
 
-        if !login_status.success() {
-            bail!("Failed to login to Proton Pass. Please run 'pass-cli login' manually.");
-        }
+/// Apply PSU_* environment variables and CLI overrides to a loaded config
+fn apply_overrides(mut config: Config, args: &Args) -> Result<Config> {
+    // Precedence: CLI flag > PSU_* environment variable > config file > default
+    config.apply_env()?;
 
-        eprintln!();
+    // Apply CLI overrides to config
+    if let Some(ref output_dir) = args.output_dir {
+        config.ssh_output_dir = output_dir.to_string_lossy().to_string();
     }
-
-    if which::which("ssh-keygen").is_err() {
-        bail!("ssh-keygen not found. Install OpenSSH first.");
+    if let Some(sync_public_key) = args.sync_public_key {
+        config.sync_public_key = sync_public_key;
     }
-
-    Ok(())
-}
-
-fn handle_purge(
-    config: &Config,
-    dry_run: bool,
-    quiet: bool,
-    do_ssh: bool,
-    do_rclone: bool,
-) -> Result<()> {
-    if !quiet {
-        println!("Purging managed resources...");
-    }
-
-    // Delete SSH keys folder
-    if do_ssh {
-        let ssh_dir = config.expanded_ssh_output_dir();
-        if ssh_dir.exists() {
-            if dry_run {
-                if !quiet {
-                    println!("  Would remove {}", ssh_dir.display());
-                }
-            } else {
-                std::fs::remove_dir_all(&ssh_dir)?;
-                if !quiet {
-                    println!("  Removed {}", ssh_dir.display());
-                }
-            }
-        } else if !quiet {
-            println!("  {} does not exist", ssh_dir.display());
-        }
+    if let Some(key_naming) = args.key_naming {
+        config.key_naming = key_naming;
     }
-
-    // Delete managed rclone remotes
-    if do_rclone {
-        rclone::purge_managed_remotes(config, dry_run, quiet)?;
+    if let Some(label_match) = args.label_match {
+        config.label_match = label_match;
     }
-
-    if !quiet {
-        println!("Done.");
+    if let Some(key_format) = args.key_format {
+        config.key_format = key_format;
     }
-    Ok(())
-}
-
-fn filter_by_patterns(items: &[String], patterns: &[String]) -> Vec<String> {
-    if patterns.is_empty() {
-        return items.to_vec();
+    if let Some(max_items) = args.max_items {
+        config.max_items = max_items;
     }
-
-    items
-        .iter()
-        .filter(|item| matches_any_pattern(item, patterns))
-        .cloned()
-        .collect()
-}
-
-fn matches_any_pattern(item: &str, patterns: &[String]) -> bool {
-    if patterns.is_empty() {
-        return true;
+    if let Some(ref key_comment) = args.key_comment {
+        config.key_comment = key_comment.clone();
     }
-
-    for pattern in patterns {
-        if let Ok(glob_pattern) = glob::Pattern::new(pattern) {
-            if glob_pattern.matches(item) {
-                return true;
-            }
-        }
+    if let Some(ref password_path) = args.rclone_password_path {
+        config.rclone.password_path = password_path.clone();
+    }
+    if let Some(ref rclone_config) = args.rclone_config {
+        config.rclone.config_path = rclone_config.to_string_lossy().to_string();
+    }
+    if args.allow_unsafe_ssh_options {
+        config.allowed_ssh_options = vec!["*".to_string()];
+    }
+    if args.always_encrypt {
+        config.rclone.always_encrypt = true;
+    }
+    if args.strict_duplicates {
+        config.strict_duplicates = true;
+    }
+    if args.case_insensitive {
+        config.case_insensitive = true;
+    }
+    if args.allow_synced_dir {
+        config.allow_synced_dir = true;
+    }
+    if args.no_upgrade_warning {
+        config.suppress_upgrade_warning = true;
     }
+    if args.read_only {
+        config.read_only = true;
+    }
+    if args.no_login {
+        config.auto_login = false;
+    }
+    if args.from_json.is_some() {
+        // The export is a snapshot: nothing goes back to the password manager
+        config.sync_public_key = SyncPublicKey::Never;
+    }
+    interactive::set_auto_login(config.auto_login);
+    if args.ascii {
+        config.theme = Theme::Ascii;
+    }
+    progress::set_theme(config.theme, config.spinner_tick);
 
-    false
+    Ok(config)
 }
 
-fn handle_from_tsh(args: &Args) -> Result<()> {
-    let dry_run = args.dry_run;
-    let quiet = args.quiet;
+fn handle_uninstall(args: &Args) -> Result<()> {
+    let config_path = args.config.clone().unwrap_or_else(Config::default_path);
+    // Don't create a config file only to remove it again
+    let config = if config_path.exists() {
+        load_config(args)?
+    } else {
+        apply_overrides(Config::default(), args)?
+    };
 
-    // Helper for logging
-    let log = |msg: &str| {
-        if !quiet {
-            println!("{}", msg);
-        }
+    let options = UninstallOptions {
+        remove_config: args.remove_config.then_some(config_path),
+        dry_run: args.dry_run,
+        quiet: args.quiet,
+        assume_yes: args.yes,
     };
+    runner::run_uninstall(&config, &options)
+}
 
-    // 1. Validate exactly one vault provided
-    if args.vault.len() != 1 {
-        anyhow::bail!("--from-tsh requires exactly one --vault (-v) argument");
-    }
-    let vault_name = &args.vault[0];
+fn run_export(args: &Args) -> Result<()> {
+    let config_path = args.config.clone().unwrap_or_else(Config::default_path);
+    let config = load_config(args)?;
 
-    // 2. Check for conflicting flags
-    if args.ssh || args.rclone || args.purge || args.full {
-        anyhow::bail!("--from-tsh cannot be used with --ssh, --rclone, --purge, or --full");
+    // Check for missing config options and warn user
+    if config_path.exists() && !config.suppress_upgrade_warning {
+        let missing = config::check_missing_options(&config_path);
+        if !missing.is_empty() && !args.quiet {
+            eprintln!(
+                "Warning: Your config is missing new options: {}",
+                missing.join(", ")
+            );
+            eprintln!("  Add them (keeping your settings) with: pass-ssh-unpack --upgrade-config");
+            eprintln!("  (or set suppress_upgrade_warning = true to hide this warning)");
+            eprintln!();
+        }
     }
 
-    if dry_run {
-        log("[DRY RUN] No changes will be made");
-        log("");
-    }
+    if args.json_lines {
+        json_lines::enable();
+    }
+
+    let options = ExportOptions {
+        vaults: args.vault.clone(),
+        items: args.item.clone(),
+        labels: args.label.clone(),
+        full: args.full,
+        only_new: args.only_new,
+        no_write_keys: args.no_write_keys,
+        add_to_agent: args.add_to_agent,
+        verify_ssh: args.verify_ssh,
+        ssh_only: args.ssh,
+        rclone_only: args.rclone,
+        purge: args.purge,
+        dry_run: args.dry_run || args.diff_ssh_config || args.export_rclone_config,
+        diff_ssh_config: args.diff_ssh_config,
+        backend: args.backend,
+        from_json: args.from_json.clone(),
+        ansible_inventory: args.ansible_inventory.clone(),
+        export_rclone_config: args.export_rclone_config,
+        rclone_export_path: args.out.clone(),
+        adopt_remotes: args.adopt_remotes,
+        assume_yes: args.yes,
+        fail_fast: args.fail_fast,
+        // Keep stdout clean when the rclone config is printed there
+        quiet: args.quiet
+            || args.summary_only
+            || args.json_lines
+            || (args.export_rclone_config && args.out.is_none()),
+    };
 
-    // 3. Check tsh is installed
-    if which::which("tsh").is_err() {
-        anyhow::bail!("tsh not found. Install Teleport CLI first.");
+    if let Some(minutes) = args.watch {
+        return watch(args, &config, &options, minutes);
     }
 
-    // 4. Check tsh login status
-    let spinner = if !quiet {
-        Some(progress::spinner("Checking Teleport login..."))
-    } else {
-        None
-    };
-
-    let teleport = Teleport::new();
-    let status = match teleport.get_status() {
-        Ok(s) => {
-            if let Some(sp) = spinner {
-                sp.finish_and_clear();
-            }
-            s
-        }
+    let start = Instant::now();
+    let report = match runner::run_export(&config, &options) {
+        Ok(report) => report,
         Err(e) => {
-            if let Some(sp) = spinner {
-                sp.finish_and_clear();
+            json_lines::emit(
+                "error",
+                serde_json::json!({ "context": "Export failed", "message": format!("{:#}", e) }),
+            );
+            if let Some(ref path) = args.report_file {
+                append_report(path, &format!("Export failed: {:#}\n", e))?;
             }
             return Err(e);
         }
     };
 
-    log(&format!(
-        "Logged in to {} as {}",
-        status.cluster, status.username
-    ));
-    log("");
-
-    // 5. Get proxy address
-    let proxy = teleport.get_proxy(&status)?;
-
-    // 6. List nodes
-    let spinner = if !quiet {
-        Some(progress::spinner("Fetching Teleport nodes..."))
+    if let Some(ref path) = args.report_file {
+        append_report(path, &report.render(options.dry_run))?;
+    }
+
+    if json_lines::is_enabled() {
+        // Errors were already streamed as they happened
+        json_lines::emit(
+            "summary",
+            serde_json::json!({
+                "hosts": report.hosts,
+                "aliases": report.aliases,
+                "unchanged_keys": report.unchanged_keys,
+                "skipped_no_key_file": report.skipped_no_key_file,
+                "rclone_entries": report.rclone_entries.len(),
+                "warnings": report.errors.warnings(),
+                "errors": report.errors.errors().len(),
+                "dry_run": options.dry_run,
+                "elapsed_ms": start.elapsed().as_millis() as u64,
+            }),
+        );
     } else {
-        None
-    };
-
-    let nodes = teleport.list_nodes()?;
-
-    if let Some(sp) = spinner {
-        sp.finish_and_clear();
-    }
-
-    // 7. Filter nodes by --item patterns (if provided)
-    let item_patterns = &args.item;
-    let filtered_nodes: Vec<_> = nodes
-        .iter()
-        .filter(|n| matches_any_pattern(n, item_patterns))
-        .collect();
+        if args.summary_only {
+            print!("{}", report.render_counts(options.dry_run));
+        }
+        if args.stats && !args.quiet {
+            report.timings.report(start.elapsed());
+        }
 
-    if filtered_nodes.is_empty() {
-        log("No nodes matched the specified patterns.");
-        return Ok(());
+        // Report any collected errors
+        report.errors.report();
     }
 
-    log(&format!(
-        "Found {} node(s) to process",
-        filtered_nodes.len()
-    ));
-    log("");
-
-    // 8. Check/create vault
-    let proton_pass = ProtonPass::new();
-
-    if !proton_pass.vault_exists(vault_name)? {
-        if dry_run {
-            log(&format!("[DRY RUN] Would create vault: {}", vault_name));
-        } else {
-            let spinner = if !quiet {
-                Some(progress::spinner(&format!(
-                    "Creating vault '{}'...",
-                    vault_name
-                )))
-            } else {
-                None
-            };
-
-            proton_pass.create_vault(vault_name)?;
-
-            if let Some(sp) = spinner {
-                sp.finish_and_clear();
-            }
-            log(&format!("Created vault: {}", vault_name));
-        }
+    if report.errors.has_errors() {
+        std::process::exit(report.errors.exit_code() as i32);
     }
 
-    // 9. Get existing items in vault (any type, not just SSH keys)
-    let existing_titles: HashSet<String> = proton_pass
-        .list_item_titles(vault_name)
-        .unwrap_or_default()
-        .into_iter()
-        .collect();
+    Ok(())
+}
 
-    // 10. Process nodes with progress bar
-    let pb = if !quiet {
-        Some(progress::node_progress_bar(filtered_nodes.len() as u64))
-    } else {
-        None
+/// `--watch`: re-run the export every `minutes`, printing what changed. Failed
+/// cycles are reported and retried on the next one; Ctrl-C exits.
+fn watch(args: &Args, config: &Config, options: &ExportOptions, minutes: u64) -> Result<()> {
+    let options = ExportOptions {
+        quiet: true,
+        ..options.clone()
     };
+    println!(
+        "Watching: exporting every {} minute(s). Press Ctrl-C to stop.",
+        minutes
+    );
 
-    let mut created = 0;
-    let mut skipped = 0;
-
-    for (i, hostname) in filtered_nodes.iter().enumerate() {
-        if existing_titles.contains(*hostname) {
-            if let Some(ref pb) = pb {
-                pb.println(format!("  {}: skipped (already exists)", hostname));
-            }
-            skipped += 1;
-        } else {
-            // Get subsystem path (skip if --no-scan)
-            let server_command = if args.no_scan {
-                "/usr/lib/openssh/sftp-server".to_string()
-            } else {
-                if let Some(ref pb) = pb {
-                    pb.set_message(format!("Finding Subsystem for {}...", hostname));
+    loop {
+        match runner::run_export(config, &options) {
+            Ok(report) => {
+                println!("[{}] {}", json_lines::timestamp(), report.change_summary());
+                report.errors.report();
+                if let Some(ref path) = args.report_file {
+                    append_report(path, &report.render(false))?;
                 }
-
-                let result = teleport
-                    .get_subsystem(hostname)
-                    .unwrap_or_else(|_| "/usr/lib/openssh/sftp-server".to_string());
-
-                if let Some(ref pb) = pb {
-                    pb.set_message("");
+            }
+            Err(e) => {
+                eprintln!("[{}] Export failed: {:#}", json_lines::timestamp(), e);
+                if let Some(ref path) = args.report_file {
+                    append_report(path, &format!("Export failed: {:#}\n", e))?;
                 }
+            }
+        }
 
-                result
-            };
-
-            // Build SSH command
-            let ssh_command = format!("tsh ssh --proxy={} {}", proxy, hostname);
-
-            if dry_run {
-                if let Some(ref pb) = pb {
-                    pb.println(format!("  {}: [DRY RUN] would create", hostname));
-                    pb.println(format!("    SSH: {}", ssh_command));
-                    pb.println(format!("    Server Command: {}", server_command));
-                }
-            } else {
-                // Create item (with spinner message on progress bar)
-                if let Some(ref pb) = pb {
-                    pb.set_message(format!("Creating {}...", hostname));
-                }
+        // Ctrl-C during the rclone step lets it finish; stop instead of sleeping
+        if interrupt::is_interrupted() {
+            return Ok(());
+        }
+        std::thread::sleep(Duration::from_secs(minutes.saturating_mul(60)));
+    }
+}
 
-                proton_pass.create_tsh_item(vault_name, hostname, &ssh_command, &server_command)?;
+/// Append a run summary to the `--report-file`, under a timestamped header
+fn append_report(path: &Path, body: &str) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open report file {}", path.display()))?;
+    write!(
+        file,
+        "=== pass-ssh-unpack run at {} ===\n{}\n",
+        json_lines::timestamp(),
+        body
+    )
+    .with_context(|| format!("Failed to write report file {}", path.display()))
+}
 
-                if let Some(ref pb) = pb {
-                    pb.set_message("");
-                    pb.println(format!("  {}: created", hostname));
-                }
-            }
-            created += 1;
+fn handle_from_tsh(args: &Args) -> Result<()> {
+    if args.direct_rclone {
+        // Straight to rclone: no vault involved, --rclone is implied
+        if !args.vault.is_empty() || args.update_existing {
+            return Err(ExitCode::Usage
+                .error("--direct-rclone cannot be used with --vault or --update-existing"));
+        }
+        if args.ssh || args.purge || args.full {
+            return Err(
+                ExitCode::Usage.error("--from-tsh cannot be used with --ssh, --purge, or --full")
+            );
+        }
+    } else if args.vault_by_label.is_some() {
+        // Vaults come from node labels; a single --vault is the fallback
+        if args.vault.len() > 1 {
+            return Err(ExitCode::Usage
+                .error("--vault-by-label takes at most one --vault (the fallback vault)"));
+        }
+        if args.ssh || args.rclone || args.purge || args.full {
+            return Err(ExitCode::Usage
+                .error("--from-tsh cannot be used with --ssh, --rclone, --purge, or --full"));
+        }
+    } else {
+        // Validate exactly one vault provided
+        if args.vault.len() != 1 {
+            return Err(
+                ExitCode::Usage.error("--from-tsh requires exactly one --vault (-v) argument")
+            );
         }
 
-        if let Some(ref pb) = pb {
-            pb.set_position(i as u64 + 1);
+        // Check for conflicting flags
+        if args.ssh || args.rclone || args.purge || args.full {
+            return Err(ExitCode::Usage
+                .error("--from-tsh cannot be used with --ssh, --rclone, --purge, or --full"));
         }
     }
 
-    if let Some(pb) = pb {
-        pb.finish_and_clear();
+    let config = load_config(args)?;
+    if config.read_only && !args.direct_rclone {
+        return Err(ExitCode::Usage.error(
+            "--from-tsh creates and updates Proton Pass items, which read-only mode forbids (use --direct-rclone to only create rclone remotes)",
+        ));
     }
+    let options = TshImportOptions {
+        vault: args.vault.first().cloned().unwrap_or_default(),
+        vault_label: args.vault_by_label.clone(),
+        items: args.item.clone(),
+        case_insensitive: config.case_insensitive,
+        no_scan: args.no_scan,
+        parallel_scan: args.parallel_tsh_scan as usize,
+        backend: args.backend,
+        assume_os: args.assume_os,
+        cluster: args.cluster.clone(),
+        proxy: args
+            .proxy
+            .clone()
+            .or_else(|| Some(config.tsh_proxy.clone()).filter(|p| !p.is_empty())),
+        direct_rclone: args.direct_rclone,
+        update_existing: args.update_existing,
+        dry_run: args.dry_run,
+        quiet: args.quiet,
+    };
 
-    log("");
-    if dry_run {
-        log(&format!(
-            "[DRY RUN] Would add {} Teleport node(s) to vault \"{}\" ({} already exist)",
-            created, vault_name, skipped
-        ));
+    let warnings = if args.direct_rclone {
+        runner::rclone_from_tsh(&config, &options)?.1
     } else {
-        log(&format!(
-            "Done! Added {} Teleport node(s) to vault \"{}\" ({} skipped)",
-            created, vault_name, skipped
-        ));
+        runner::import_from_tsh(&options)?.warnings
+    };
+    if !warnings.is_empty() {
+        eprintln!();
+        eprintln!("{} warning(s):", warnings.len());
+        for warning in &warnings {
+            eprintln!("  - {}", warning);
+        }
     }
-
     Ok(())
 }
 
-fn run_interactive_mode() -> Result<()> {
+fn run_interactive_mode(assume_yes: bool, ascii: bool) -> Result<()> {
     loop {
-        match interactive::run_interactive()? {
+        match interactive::run_interactive(assume_yes)? {
             InteractiveAction::Cancelled => {
                 println!();
                 println!("Thanks for using pass-ssh-unpack!");
@@ -643,13 +579,16 @@ fn run_interactive_mode() -> Result<()> {
                 vault,
                 item_pattern,
                 scan_remotes,
+                cluster,
                 dry_run,
             } => {
                 println!();
                 // Build args for handle_from_tsh
                 let mut args = Args::parse_from(["pass-ssh-unpack"]);
+                args.ascii = ascii;
                 args.from_tsh = true;
                 args.vault = vec![vault];
+                args.cluster = cluster;
                 args.no_scan = !scan_remotes;
                 args.dry_run = dry_run;
                 if let Some(pattern) = item_pattern {
@@ -671,6 +610,8 @@ fn run_interactive_mode() -> Result<()> {
                 println!();
                 // Build args for run_export
                 let mut args = Args::parse_from(["pass-ssh-unpack"]);
+                args.ascii = ascii;
+                args.yes = assume_yes;
                 args.dry_run = dry_run;
                 args.full = full;
                 args.vault = vaults;
@@ -695,6 +636,7 @@ fn run_interactive_mode() -> Result<()> {
                 println!();
                 // Build args for handle_purge
                 let mut args = Args::parse_from(["pass-ssh-unpack"]);
+                args.ascii = ascii;
                 args.purge = true;
                 args.dry_run = dry_run;
 
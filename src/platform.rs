@@ -36,11 +36,28 @@ pub fn get_hostname() -> String {
 /// Set file permissions to be readable/writable only by owner (600 on Unix)
 #[cfg(unix)]
 pub fn set_private_permissions(path: &Path) -> Result<()> {
+    set_mode(path, 0o600)
+}
+
+/// Set the permission bits of a file or directory (e.g. 0o600, 0o700)
+#[cfg(unix)]
+pub fn set_mode(path: &Path, mode: u32) -> Result<()> {
     use std::os::unix::fs::PermissionsExt;
-    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
     Ok(())
 }
 
+/// Best effort on Windows: files are restricted to the current user with
+/// icacls. Directories are left alone, since dropping their inherited ACLs
+/// would also strip access from files created in them later.
+#[cfg(windows)]
+pub fn set_mode(path: &Path, _mode: u32) -> Result<()> {
+    if path.is_dir() {
+        return Ok(());
+    }
+    set_private_permissions(path)
+}
+
 /// Set file permissions on Windows using icacls
 #[cfg(windows)]
 pub fn set_private_permissions(path: &Path) -> Result<()> {
@@ -71,8 +88,90 @@ pub fn set_private_permissions(path: &Path) -> Result<()> {
     Ok(())
 }
 
-/// Get the home directory path string for use in SSH config
-/// Returns %d (SSH config placeholder for home directory)
-pub fn ssh_home_placeholder() -> &'static str {
-    "%d"
+/// Editor command for `--edit-config`: `$VISUAL`, then `$EDITOR` (both may
+/// include arguments, e.g. "code --wait"), then a platform default
+pub fn editor_command() -> Vec<String> {
+    for var in ["VISUAL", "EDITOR"] {
+        if let Ok(value) = std::env::var(var) {
+            let words: Vec<String> = value.split_whitespace().map(String::from).collect();
+            if !words.is_empty() {
+                return words;
+            }
+        }
+    }
+
+    let fallback: &[&str] = if cfg!(windows) {
+        &["notepad"]
+    } else if cfg!(target_os = "macos") {
+        // Open in TextEdit and wait until it is closed
+        &["open", "-W", "-t"]
+    } else if which::which("nano").is_ok() {
+        &["nano"]
+    } else {
+        &["vi"]
+    };
+    fallback.iter().map(|s| s.to_string()).collect()
+}
+
+/// Extra `ssh-add` arguments. On macOS the key's passphrase is also stored in
+/// the keychain, with `--apple-use-keychain` if this ssh-add knows it (macOS 12+).
+pub fn ssh_add_args() -> Vec<&'static str> {
+    #[cfg(target_os = "macos")]
+    {
+        // Unknown options make ssh-add print its usage, which lists the flag when supported
+        if let Ok(output) = std::process::Command::new("ssh-add").arg("-?").output() {
+            let usage = String::from_utf8_lossy(&output.stderr);
+            if usage.contains("--apple-use-keychain") {
+                return vec!["--apple-use-keychain"];
+            }
+        }
+    }
+    Vec::new()
+}
+
+/// Reference to a file as written into the SSH config (`IdentityFile`,
+/// `CertificateFile`, ...). Paths under the home directory use `%d`, ssh's
+/// home directory token, so the config stays portable.
+#[cfg(unix)]
+pub fn ssh_config_path(path: &Path) -> String {
+    if let Some(home) = dirs::home_dir() {
+        if let Ok(rel) = path.strip_prefix(&home) {
+            return format!("%d/{}", rel.to_string_lossy());
+        }
+    }
+    path.to_string_lossy().into_owned()
+}
+
+/// OpenSSH for Windows resolves `%d` from its own notion of the home directory,
+/// which need not be the profile directory the keys were written to, so the
+/// absolute path is used. Forward slashes are accepted by the Windows client and,
+/// unlike backslashes, need no escaping inside the quoted directive
+/// (`IdentityFile "C:/Users/me/.ssh/proton-pass/github"`).
+#[cfg(windows)]
+pub fn ssh_config_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn mode(path: &Path) -> u32 {
+        std::fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn set_mode_applies_permission_bits() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("key");
+        std::fs::write(&file, "secret").unwrap();
+
+        set_mode(&file, 0o600).unwrap();
+        assert_eq!(mode(&file), 0o600);
+        set_mode(dir.path(), 0o700).unwrap();
+        assert_eq!(mode(dir.path()), 0o700);
+        set_mode(dir.path(), 0o750).unwrap();
+        assert_eq!(mode(dir.path()), 0o750);
+    }
 }
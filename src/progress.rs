@@ -1,52 +1,124 @@
 use indicatif::{ProgressBar, ProgressStyle};
+use std::sync::Mutex;
 use std::time::Duration;
 
+use crate::config::Theme;
+
+/// Characters and behavior shared by all spinners and progress bars
+#[derive(Debug, Clone, Copy)]
+struct Style {
+    tick_chars: &'static str,
+    progress_chars: &'static str,
+    steady_tick: bool,
+}
+
+const UNICODE: Style = Style {
+    tick_chars: "⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏",
+    progress_chars: "━━─",
+    steady_tick: true,
+};
+
+const ASCII: Style = Style {
+    tick_chars: "|/-\\|",
+    progress_chars: "##-",
+    steady_tick: true,
+};
+
+/// Style used by the constructors below (see `set_theme`)
+static STYLE: Mutex<Style> = Mutex::new(UNICODE);
+
+/// Select the spinner/bar characters (`theme` in the config, `--ascii`) and
+/// whether spinners animate on a timer
+pub fn set_theme(theme: Theme, steady_tick: bool) {
+    let unicode = match theme {
+        Theme::Auto => supports_unicode(),
+        Theme::Unicode => true,
+        Theme::Ascii => false,
+    };
+    let style = Style {
+        steady_tick,
+        ..if unicode { UNICODE } else { ASCII }
+    };
+    *STYLE.lock().unwrap_or_else(|e| e.into_inner()) = style;
+}
+
+fn style() -> Style {
+    *STYLE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Guess whether the terminal can draw the Unicode characters: the Linux
+/// console and dumb terminals can't, nor can a non-UTF-8 locale
+fn supports_unicode() -> bool {
+    if let Ok(term) = std::env::var("TERM") {
+        if term == "linux" || term == "dumb" {
+            return false;
+        }
+    }
+
+    // The first locale variable that is set decides, as for the C library
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .is_none_or(|locale| {
+            let locale = locale.to_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        })
+}
+
+/// Create a progress bar with the given template in the current theme
+fn bar(len: u64, template: &str) -> ProgressBar {
+    let pb = ProgressBar::new(len);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(template)
+            .unwrap()
+            .progress_chars(style().progress_chars),
+    );
+    pb
+}
+
 /// Create a spinner for indeterminate operations
 pub fn spinner(message: &str) -> ProgressBar {
+    let style = style();
     let pb = ProgressBar::new_spinner();
     pb.set_style(
         ProgressStyle::default_spinner()
             .template("{spinner:.cyan} {msg}")
             .unwrap()
-            .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏"),
+            .tick_chars(style.tick_chars),
     );
     pb.set_message(message.to_string());
-    pb.enable_steady_tick(Duration::from_millis(80));
+    if style.steady_tick {
+        pb.enable_steady_tick(Duration::from_millis(80));
+    }
     pb
 }
 
 /// Create a progress bar for vault processing
 pub fn vault_progress_bar(len: u64) -> ProgressBar {
-    let pb = ProgressBar::new(len);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("Processing vaults [{bar:20.cyan/dim}] {pos}/{len}")
-            .unwrap()
-            .progress_chars("━━─"),
-    );
-    pb
+    bar(len, "Processing vaults [{bar:20.cyan/dim}] {pos}/{len}")
+}
+
+/// Create a progress bar for the items of the current vault (nested under the vault bar)
+pub fn item_progress_bar(len: u64) -> ProgressBar {
+    bar(len, "  Items [{bar:20.cyan/dim}] {pos}/{len} {msg}")
 }
 
 /// Create a progress bar for rclone operations with current item display
 pub fn rclone_progress_bar(len: u64) -> ProgressBar {
-    let pb = ProgressBar::new(len);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("  [{bar:20.cyan/dim}] {pos}/{len} {msg}")
-            .unwrap()
-            .progress_chars("━━─"),
-    );
-    pb
+    bar(len, "  [{bar:20.cyan/dim}] {pos}/{len} {msg}")
+}
+
+/// Create a progress bar for the sftp-server scan of Teleport nodes
+pub fn scan_progress_bar(len: u64) -> ProgressBar {
+    bar(len, "Scanning Nodes [{bar:20.cyan/dim}] {pos}/{len} {msg}")
 }
 
 /// Create a progress bar for Teleport node processing
 pub fn node_progress_bar(len: u64) -> ProgressBar {
-    let pb = ProgressBar::new(len);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("Processing Nodes [{bar:20.cyan/dim}] {pos}/{len} {msg}")
-            .unwrap()
-            .progress_chars("━━─"),
-    );
-    pb
+    bar(
+        len,
+        "Processing Nodes [{bar:20.cyan/dim}] {pos}/{len} {msg}",
+    )
 }
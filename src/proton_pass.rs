@@ -1,9 +1,126 @@
 use anyhow::{Context, Result};
 use serde::Deserialize;
-use std::process::Command;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::backend::SecretBackend;
+use crate::config::FieldNames;
+use crate::error::ExitCode;
 
 /// Interface to Proton Pass CLI
-pub struct ProtonPass;
+pub struct ProtonPass {
+    /// Extra field names item values are read from
+    field_names: FieldNames,
+    /// Problems with single items found while listing (see `take_warnings`)
+    warnings: Mutex<Vec<String>>,
+}
+
+/// Set once a mid-run re-login has been attempted (it is only tried once)
+static RELOGIN_ATTEMPTED: AtomicBool = AtomicBool::new(false);
+
+/// Set when the session expired and logging in again failed
+static SESSION_LOST: AtomicBool = AtomicBool::new(false);
+
+const SESSION_LOST_MESSAGE: &str =
+    "Proton Pass session expired and was not renewed. Run 'pass-cli login' and try again.";
+
+/// Whether pass-cli stderr says the session is missing or expired
+fn is_session_expired(stderr: &[u8]) -> bool {
+    let stderr = String::from_utf8_lossy(stderr).to_lowercase();
+    [
+        "not logged in",
+        "session expired",
+        "session has expired",
+        "invalid session",
+        "login required",
+        "unauthorized",
+    ]
+    .iter()
+    .any(|signature| stderr.contains(signature))
+}
+
+/// Log in to Proton Pass interactively (pass-cli prompts on the terminal)
+pub fn login() -> Result<()> {
+    let status = Command::new("pass-cli")
+        .arg("login")
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .status()
+        .context("Failed to execute pass-cli login")?;
+
+    if !status.success() {
+        return Err(ExitCode::Auth
+            .error("Failed to login to Proton Pass. Please run 'pass-cli login' manually."));
+    }
+    Ok(())
+}
+
+/// Fail with a single clear error once the session has been lost, so a long
+/// run stops instead of collecting the same auth error for every item
+pub fn ensure_session() -> Result<()> {
+    if SESSION_LOST.load(Ordering::SeqCst) {
+        return Err(ExitCode::Auth.error(SESSION_LOST_MESSAGE));
+    }
+    Ok(())
+}
+
+/// Run pass-cli with `args`. If the session expired mid-run, log in again
+/// (once per process, only on a terminal) and retry the command.
+fn pass_cli(args: &[&str]) -> std::io::Result<Output> {
+    let output = Command::new("pass-cli").args(args).output()?;
+    if output.status.success() || !is_session_expired(&output.stderr) {
+        return Ok(output);
+    }
+
+    if SESSION_LOST.load(Ordering::SeqCst) || RELOGIN_ATTEMPTED.swap(true, Ordering::SeqCst) {
+        return Ok(output);
+    }
+
+    eprintln!();
+    if !crate::interactive::can_login() {
+        eprintln!("Proton Pass session expired. Run 'pass-cli login' and try again (automatic login is off: --no-login, auto_login = false, or no terminal).");
+        SESSION_LOST.store(true, Ordering::SeqCst);
+        return Ok(output);
+    }
+    eprintln!("Proton Pass session expired. Launching login...");
+    if login().is_err() {
+        SESSION_LOST.store(true, Ordering::SeqCst);
+        return Ok(output);
+    }
+    eprintln!();
+
+    Command::new("pass-cli").args(args).output()
+}
+
+/// `--name=value` for a flag whose value is a user-chosen name. Attached with
+/// `=` so a name starting with `-` (e.g. "-Archive") is not read as a flag.
+fn flag_arg(name: &str, value: &str) -> String {
+    format!("{}={}", name, value)
+}
+
+/// Arguments for one page of `pass-cli item list`. The vault goes last,
+/// after `--`, so a name like "-Archive" stays positional.
+fn item_list_args<'a>(
+    vault: &'a str,
+    filter_type: Option<&'a str>,
+    cursor: Option<&'a str>,
+) -> Vec<&'a str> {
+    let mut args = vec!["item", "list"];
+    if let Some(filter_type) = filter_type {
+        args.extend(["--filter-type", filter_type]);
+    }
+    args.extend(["--filter-state", "active", "--output", "json"]);
+    if let Some(cursor) = cursor {
+        args.extend(["--cursor", cursor]);
+    }
+    args.extend(["--", vault]);
+    args
+}
 
 #[derive(Debug, Deserialize)]
 pub struct VaultListResponse {
@@ -18,22 +135,51 @@ pub struct Vault {
 #[derive(Debug, Deserialize)]
 pub struct ItemListResponse {
     pub items: Vec<Item>,
+    /// Cursor for the next page, when pass-cli paginates the listing
+    #[serde(default, alias = "nextCursor", alias = "next_page_token")]
+    pub next_cursor: Option<String>,
+    /// Total number of matching items, when reported
+    #[serde(default)]
+    pub total: Option<usize>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Item {
     pub content: ItemContent,
+    /// Labels (tags), when pass-cli reports them
+    #[serde(default, alias = "tags")]
+    pub labels: Option<Vec<String>>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
+pub struct Attachment {
+    pub id: String,
+    #[serde(alias = "filename", alias = "file_name")]
+    pub name: String,
+}
+
+impl Attachment {
+    /// Check if the attachment looks like a private key file (id_*, *.pem, *.key)
+    pub fn is_private_key(&self) -> bool {
+        let name = self.name.to_lowercase();
+        !name.ends_with(".pub")
+            && (name.starts_with("id_") || name.ends_with(".pem") || name.ends_with(".key"))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct ItemContent {
     pub title: String,
+    #[serde(default)]
+    pub note: String,
     pub content: ItemData,
     #[serde(default)]
     pub extra_fields: Vec<ExtraField>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct ItemData {
     #[serde(rename = "SshKey")]
     pub ssh_key: Option<SshKeyData>,
@@ -41,221 +187,480 @@ pub struct ItemData {
     pub custom: Option<CustomData>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct SshKeyData {
     pub private_key: Option<String>,
     pub public_key: Option<String>,
+    #[serde(default)]
+    pub sections: Vec<CustomSection>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct CustomData {
     #[serde(default)]
     pub sections: Vec<CustomSection>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct CustomSection {
     pub section_name: String,
     #[serde(default)]
     pub section_fields: Vec<SectionField>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct SectionField {
     pub name: String,
     pub content: FieldContent,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct ExtraField {
     pub name: String,
     pub content: FieldContent,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct FieldContent {
     #[serde(rename = "Text")]
     pub text: Option<String>,
 }
 
+/// Name of the custom section holding Teleport/rclone fields
+const TELEPORT_SECTION: &str = "Teleport Rclone Config";
+
+impl ItemData {
+    /// Find the "Teleport Rclone Config" section, whether it lives on the
+    /// custom content or on an SSH key item's own sections
+    fn teleport_section(&self) -> Option<&CustomSection> {
+        let custom_sections = self.custom.iter().flat_map(|c| c.sections.iter());
+        let key_sections = self.ssh_key.iter().flat_map(|k| k.sections.iter());
+        custom_sections
+            .chain(key_sections)
+            .find(|s| s.section_name == TELEPORT_SECTION)
+    }
+}
+
 /// Simplified SSH item for processing
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct SshItem {
     pub title: String,
     pub private_key: Option<String>,
     pub public_key: Option<String>,
     pub host: Option<String>,
     pub username: Option<String>,
+    /// SSH port (validated when the item is processed)
+    pub port: Option<String>,
     pub aliases: Option<String>,
     pub ssh: Option<String>,
     pub server_command: Option<String>,
     pub jump: Option<String>,
+    /// Bastion command for a `ProxyCommand` directive (exclusive with `jump`)
+    pub proxy_command: Option<String>,
+    /// Extra SSH directives, one "Directive value" per line
+    pub options: Option<String>,
+    /// SSH agent socket to authenticate with (1Password, gpg-agent, ...)
+    pub identity_agent: Option<String>,
+    /// Match criteria that wrap the host's stanza in a `Match` block
+    pub match_condition: Option<String>,
+    /// `HostKeyAlgorithms` value (validated when the item is processed)
+    pub host_key_algorithms: Option<String>,
+    /// `PubkeyAcceptedAlgorithms` value (validated when the item is processed)
+    pub pubkey_algorithms: Option<String>,
+    /// Signed OpenSSH certificate (`*-cert.pub` content) for the key
+    pub certificate: Option<String>,
+    /// `LocalForward` specs, comma- or newline-separated (validated when processed)
+    pub local_forward: Option<String>,
+    /// `RemoteForward` specs, comma- or newline-separated
+    pub remote_forward: Option<String>,
+    /// `DynamicForward` specs, comma- or newline-separated
+    pub dynamic_forward: Option<String>,
+    /// Share one connection per host (truthy "Multiplex" field)
+    pub multiplex: bool,
+    /// rclone `shell_type` for non-standard remote shells (e.g. "cmd", "powershell")
+    pub shell_type: Option<String>,
+    /// rclone `md5sum_command` override
+    pub md5sum_command: Option<String>,
+    /// rclone `sha1sum_command` override
+    pub sha1sum_command: Option<String>,
+    /// The item's note, kept as comments with `include_notes`
+    pub note: Option<String>,
+    /// Proton Pass labels (None when pass-cli doesn't report them)
+    pub labels: Option<Vec<String>>,
+    /// Kept in Proton Pass but not extracted ("Disabled" or "pass-ssh-unpack: skip" field)
+    pub disabled: bool,
+    /// Another item's Host this key-less item is an alias of ("Alias Of" or "Target" field)
+    pub alias_of: Option<String>,
 }
 
 impl ProtonPass {
     pub fn new() -> Self {
-        Self
+        Self::with_field_names(FieldNames::default())
     }
 
-    /// List all vault names
-    pub fn list_vaults(&self) -> Result<Vec<String>> {
-        let output = Command::new("pass-cli")
-            .args(["vault", "list", "--output", "json"])
-            .output()
-            .context("Failed to execute pass-cli vault list")?;
+    /// Read item values from custom extra field names (`[field_names]` in the config)
+    pub fn with_field_names(field_names: FieldNames) -> Self {
+        Self {
+            field_names,
+            warnings: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Record a warning for the run summary
+    fn warn(&self, warning: String) {
+        self.warnings
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(warning);
+    }
+
+    /// List SSH key items in a vault
+    pub fn list_ssh_keys(&self, vault: &str) -> Result<Vec<SshItem>> {
+        let items = self
+            .list_items(vault, Some("ssh-key"))?
+            .into_iter()
+            .map(|item| self.to_ssh_item(item))
+            .collect();
+
+        Ok(items)
+    }
+
+    /// List the active items in a vault (optionally of one type), following
+    /// pass-cli's page cursor until every page is fetched. A listing that
+    /// reports more items than it returned gets a warning, and a page that
+    /// fails after the first is an error, instead of being silently incomplete.
+    fn list_items(&self, vault: &str, filter_type: Option<&str>) -> Result<Vec<Item>> {
+        let mut items = Vec::new();
+        let mut cursor: Option<String> = None;
+        let mut total = None;
+
+        loop {
+            let args = item_list_args(vault, filter_type, cursor.as_deref());
+            let output = pass_cli(&args).context("Failed to execute pass-cli item list")?;
+
+            // Empty vault (or no items of the type) returns non-zero or empty output;
+            // past the first page that means the listing broke off
+            if !output.status.success() || output.stdout.is_empty() {
+                if cursor.is_some() {
+                    anyhow::bail!(
+                        "pass-cli item list failed after {} items in vault '{}': {}",
+                        items.len(),
+                        vault,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    );
+                }
+                break;
+            }
+
+            let response: ItemListResponse = serde_json::from_slice(&output.stdout)
+                .context("Failed to parse item list response")?;
+            total = response.total.or(total);
+            let page_len = response.items.len();
+            items.extend(response.items);
+
+            match response.next_cursor.filter(|c| !c.is_empty()) {
+                // An empty page with a cursor would loop forever
+                Some(next) if page_len > 0 && cursor.as_deref() != Some(next.as_str()) => {
+                    cursor = Some(next)
+                }
+                _ => break,
+            }
+        }
+
+        if let Some(total) = total {
+            if items.len() < total {
+                self.warn(format!(
+                    "pass-cli listed {} of {} items in vault '{}'; the rest were not processed. Please report this with your pass-cli version.",
+                    items.len(),
+                    total,
+                    vault
+                ));
+            }
+        }
+
+        Ok(items)
+    }
+
+    /// List items of any type whose private key is stored as a file attachment.
+    /// Costs one extra pass-cli call per matching item to download the key.
+    pub fn list_attachment_keys(&self, vault: &str) -> Result<Vec<SshItem>> {
+        let mut items = Vec::new();
+        for item in self.list_items(vault, None)? {
+            let has_native_key = item
+                .content
+                .content
+                .ssh_key
+                .as_ref()
+                .and_then(|k| k.private_key.as_ref())
+                .is_some_and(|k| !k.is_empty());
+            if has_native_key {
+                continue;
+            }
+
+            let attachment_id = match item.attachments.iter().find(|a| a.is_private_key()) {
+                Some(attachment) => attachment.id.clone(),
+                None => continue,
+            };
+
+            // A bad attachment only costs its own item, not the vault
+            let private_key =
+                match self.download_attachment(vault, &item.content.title, &attachment_id) {
+                    Ok(private_key) => private_key,
+                    Err(e) => {
+                        self.warn(format!(
+                            "'{}' in vault '{}': key attachment skipped: {:#}",
+                            item.content.title, vault, e
+                        ));
+                        continue;
+                    }
+                };
+            let mut ssh_item = self.to_ssh_item(item);
+            ssh_item.private_key = Some(private_key);
+            items.push(ssh_item);
+        }
+
+        Ok(items)
+    }
+
+    /// Download an item attachment and return its content as text
+    fn download_attachment(&self, vault: &str, title: &str, attachment_id: &str) -> Result<String> {
+        let temp_file =
+            tempfile::NamedTempFile::new().context("Failed to create temp file for attachment")?;
+
+        let output_path = temp_file.path().to_string_lossy().to_string();
+        let output = pass_cli(&[
+            "item",
+            "attachment",
+            "download",
+            &flag_arg("--vault-name", vault),
+            &flag_arg("--item-title", title),
+            &flag_arg("--attachment-id", attachment_id),
+            "--output",
+            &output_path,
+        ])
+        .context("Failed to execute pass-cli item attachment download")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "pass-cli vault list failed: {}",
-                String::from_utf8_lossy(&output.stderr)
+                "Failed to download attachment of '{}': {}",
+                title,
+                String::from_utf8_lossy(&output.stderr).trim()
             );
         }
 
-        let response: VaultListResponse = serde_json::from_slice(&output.stdout)
-            .context("Failed to parse vault list response")?;
+        let content = std::fs::read_to_string(temp_file.path())
+            .with_context(|| format!("Attachment of '{}' is not a text key file", title))?;
 
-        Ok(response
-            .vaults
-            .into_iter()
-            .map(|v| v.name)
-            .filter(|name| name != "Trash")
-            .collect())
+        Ok(content.trim().to_string())
     }
 
-    /// List SSH key items in a vault
-    pub fn list_ssh_keys(&self, vault: &str) -> Result<Vec<SshItem>> {
-        let output = Command::new("pass-cli")
-            .args([
-                "item",
-                "list",
-                vault,
-                "--filter-type",
-                "ssh-key",
-                "--filter-state",
-                "active",
-                "--output",
-                "json",
-            ])
-            .output()
-            .context("Failed to execute pass-cli item list")?;
-
-        // Empty vault or no SSH keys returns non-zero or empty output
-        if !output.status.success() || output.stdout.is_empty() {
-            return Ok(Vec::new());
+    /// Convert a pass-cli item into an SshItem, reading key material and extra fields
+    fn to_ssh_item(&self, item: Item) -> SshItem {
+        let names = &self.field_names;
+        let section = item.content.content.teleport_section();
+        let section_ssh = section.and_then(|s| Self::get_section_field(&s.section_fields, "SSH"));
+        let section_server_command =
+            section.and_then(|s| Self::get_section_field(&s.section_fields, "Server Command"));
+
+        let host = Self::get_field(&item.content.extra_fields, &names.host);
+        let username = Self::get_field(&item.content.extra_fields, &names.username);
+        let port = Self::get_field(&item.content.extra_fields, "Port");
+        let aliases = Self::get_field(&item.content.extra_fields, &names.aliases);
+        // Extra fields take precedence over a Teleport section on the same item
+        let ssh = Self::get_field(&item.content.extra_fields, &names.ssh).or(section_ssh);
+        let server_command = Self::get_field(&item.content.extra_fields, &names.server_command)
+            .or(section_server_command);
+        let jump = Self::get_field(&item.content.extra_fields, &names.jump);
+        let proxy_command = Self::get_field(&item.content.extra_fields, "Proxy Command");
+        let options = Self::get_field(&item.content.extra_fields, "Options");
+        let identity_agent = Self::get_field(&item.content.extra_fields, "IdentityAgent");
+        let match_condition = Self::get_field(&item.content.extra_fields, "Match");
+        let host_key_algorithms =
+            Self::get_field(&item.content.extra_fields, "Host Key Algorithms");
+        let pubkey_algorithms = Self::get_field(&item.content.extra_fields, "Pubkey Algorithms");
+        let certificate = Self::get_field(&item.content.extra_fields, "Certificate");
+        let local_forward = Self::get_field(&item.content.extra_fields, "Local Forward");
+        let remote_forward = Self::get_field(&item.content.extra_fields, "Remote Forward");
+        let dynamic_forward = Self::get_field(&item.content.extra_fields, "Dynamic Forward");
+        let multiplex = Self::get_field(&item.content.extra_fields, "Multiplex")
+            .is_some_and(|value| is_truthy(&value));
+        let shell_type = Self::get_field(&item.content.extra_fields, "Shell Type");
+        let md5sum_command = Self::get_field(&item.content.extra_fields, "Md5sum Command");
+        let sha1sum_command = Self::get_field(&item.content.extra_fields, "Sha1sum Command");
+        let disabled = Self::get_field(&item.content.extra_fields, "Disabled")
+            .or_else(|| Self::get_field(&item.content.extra_fields, "pass-ssh-unpack: skip"))
+            .is_some_and(|value| is_truthy(&value));
+        let alias_of = Self::get_field(&item.content.extra_fields, "Alias Of")
+            .or_else(|| Self::get_field(&item.content.extra_fields, "Target"));
+
+        let note = Some(item.content.note).filter(|note| !note.trim().is_empty());
+
+        let ssh_key = item.content.content.ssh_key;
+        let (private_key, public_key) = ssh_key
+            .map(|k| (k.private_key, k.public_key))
+            .unwrap_or((None, None));
+
+        SshItem {
+            title: item.content.title,
+            private_key,
+            public_key,
+            host,
+            username,
+            port,
+            aliases,
+            ssh,
+            server_command,
+            jump,
+            proxy_command,
+            options,
+            identity_agent,
+            match_condition,
+            host_key_algorithms,
+            pubkey_algorithms,
+            certificate,
+            local_forward,
+            remote_forward,
+            dynamic_forward,
+            multiplex,
+            shell_type,
+            md5sum_command,
+            sha1sum_command,
+            note,
+            labels: item.labels,
+            disabled,
+            alias_of,
         }
+    }
 
-        let response: ItemListResponse =
-            serde_json::from_slice(&output.stdout).context("Failed to parse item list response")?;
+    /// A custom item with a "Teleport Rclone Config" section as an item with
+    /// just its SSH/Server Command (None for other custom items)
+    fn teleport_item(item: Item) -> Option<SshItem> {
+        // Check if this is a Teleport item by looking for the section
+        let teleport_section = item.content.content.teleport_section()?;
 
-        let items = response
-            .items
-            .into_iter()
-            .map(|item| {
-                let ssh_key = item.content.content.ssh_key;
-                let (private_key, public_key) = ssh_key
-                    .map(|k| (k.private_key, k.public_key))
-                    .unwrap_or((None, None));
-
-                let host = Self::get_field(&item.content.extra_fields, "Host");
-                let username = Self::get_field(&item.content.extra_fields, "Username");
-                let aliases = Self::get_field(&item.content.extra_fields, "Aliases");
-                let ssh = Self::get_field(&item.content.extra_fields, "SSH");
-                let server_command = Self::get_field(&item.content.extra_fields, "Server Command");
-                let jump = Self::get_field(&item.content.extra_fields, "Jump");
-
-                SshItem {
-                    title: item.content.title,
-                    private_key,
-                    public_key,
-                    host,
-                    username,
-                    aliases,
-                    ssh,
-                    server_command,
-                    jump,
-                }
-            })
-            .collect();
+        // Extract fields from the section
+        let ssh = Self::get_section_field(&teleport_section.section_fields, "SSH");
+        let server_command =
+            Self::get_section_field(&teleport_section.section_fields, "Server Command");
 
-        Ok(items)
+        // Only include if we have at least SSH or Server Command
+        if ssh.is_none() && server_command.is_none() {
+            return None;
+        }
+
+        Some(SshItem {
+            title: item.content.title,
+            private_key: None,
+            public_key: None,
+            host: None,
+            username: None,
+            port: None,
+            aliases: None,
+            ssh,
+            server_command,
+            jump: None,
+            proxy_command: None,
+            options: None,
+            identity_agent: None,
+            match_condition: None,
+            host_key_algorithms: None,
+            pubkey_algorithms: None,
+            certificate: None,
+            local_forward: None,
+            remote_forward: None,
+            dynamic_forward: None,
+            multiplex: false,
+            shell_type: None,
+            md5sum_command: None,
+            sha1sum_command: None,
+            note: None,
+            labels: None,
+            disabled: false,
+            alias_of: None,
+        })
     }
 
-    /// List custom items with "Teleport Rclone Config" section in a vault
-    pub fn list_teleport_items(&self, vault: &str) -> Result<Vec<SshItem>> {
-        let output = Command::new("pass-cli")
-            .args([
-                "item",
-                "list",
-                vault,
-                "--filter-type",
-                "custom",
-                "--filter-state",
-                "active",
-                "--output",
-                "json",
-            ])
-            .output()
-            .context("Failed to execute pass-cli item list")?;
-
-        // Empty vault or no custom items returns non-zero or empty output
-        if !output.status.success() || output.stdout.is_empty() {
-            return Ok(Vec::new());
-        }
+    fn get_field(fields: &[ExtraField], name: &str) -> Option<String> {
+        fields
+            .iter()
+            .find(|f| f.name == name)
+            .and_then(|f| f.content.text.clone())
+            .filter(|s| !s.is_empty())
+    }
+
+    fn get_section_field(fields: &[SectionField], name: &str) -> Option<String> {
+        fields
+            .iter()
+            .find(|f| f.name == name)
+            .and_then(|f| f.content.text.clone())
+            .filter(|s| !s.is_empty())
+    }
+}
 
-        let response: ItemListResponse =
-            serde_json::from_slice(&output.stdout).context("Failed to parse item list response")?;
+impl SecretBackend for ProtonPass {
+    fn name(&self) -> &'static str {
+        "Proton Pass"
+    }
 
-        let items = response
-            .items
-            .into_iter()
-            .filter_map(|item| {
-                // Check if this is a Teleport item by looking for the section
-                let custom = item.content.content.custom?;
-                let teleport_section = custom
-                    .sections
-                    .iter()
-                    .find(|s| s.section_name == "Teleport Rclone Config")?;
-
-                // Extract fields from the section
-                let ssh = Self::get_section_field(&teleport_section.section_fields, "SSH");
-                let server_command =
-                    Self::get_section_field(&teleport_section.section_fields, "Server Command");
-
-                // Only include if we have at least SSH or Server Command
-                if ssh.is_none() && server_command.is_none() {
-                    return None;
-                }
+    fn take_warnings(&self) -> Vec<String> {
+        std::mem::take(&mut *self.warnings.lock().unwrap_or_else(|e| e.into_inner()))
+    }
 
-                Some(SshItem {
-                    title: item.content.title,
-                    private_key: None,
-                    public_key: None,
-                    host: None,
-                    username: None,
-                    aliases: None,
-                    ssh,
-                    server_command,
-                    jump: None,
-                })
-            })
-            .collect();
+    /// List all vault names, including reserved ones like Trash
+    fn list_all_vaults(&self) -> Result<Vec<String>> {
+        let output = pass_cli(&["vault", "list", "--output", "json"])
+            .context("Failed to execute pass-cli vault list")?;
 
-        Ok(items)
+        if !output.status.success() {
+            anyhow::bail!(
+                "pass-cli vault list failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        let response: VaultListResponse = serde_json::from_slice(&output.stdout)
+            .context("Failed to parse vault list response")?;
+
+        Ok(response.vaults.into_iter().map(|v| v.name).collect())
     }
 
-    /// List all processable items in a vault (SSH keys + Teleport custom items)
-    pub fn list_all_items(&self, vault: &str) -> Result<Vec<SshItem>> {
+    /// List custom items with "Teleport Rclone Config" section in a vault
+    fn list_teleport_items(&self, vault: &str) -> Result<Vec<SshItem>> {
+        Ok(self
+            .list_items(vault, Some("custom"))?
+            .into_iter()
+            .filter_map(Self::teleport_item)
+            .collect())
+    }
+
+    /// List all processable items in a vault (SSH keys + Teleport custom items,
+    /// plus keys stored as attachments when `include_attachments` is set).
+    /// Items are merged by title, so an SSH key that also carries a Teleport
+    /// section is returned once with both its key material and SSH/Server Command.
+    fn list_all_items(&self, vault: &str, include_attachments: bool) -> Result<Vec<SshItem>> {
         let mut items = self.list_ssh_keys(vault)?;
-        items.extend(self.list_teleport_items(vault)?);
+
+        if include_attachments {
+            for attachment_item in self.list_attachment_keys(vault)? {
+                match items.iter_mut().find(|i| i.title == attachment_item.title) {
+                    Some(existing) => {
+                        if existing.private_key.is_none() {
+                            existing.private_key = attachment_item.private_key;
+                        }
+                    }
+                    None => items.push(attachment_item),
+                }
+            }
+        }
+
+        merge_teleport_items(&mut items, self.list_teleport_items(vault)?);
         Ok(items)
     }
 
     /// Get a field value from a pass URI (e.g., pass://Vault/Item/password)
-    pub fn get_item_field(&self, path: &str) -> Result<String> {
-        let output = Command::new("pass-cli")
-            .args(["item", "view", path])
-            .output()
-            .context("Failed to execute pass-cli item view")?;
+    fn get_item_field(&self, path: &str) -> Result<String> {
+        let output =
+            pass_cli(&["item", "view", path]).context("Failed to execute pass-cli item view")?;
 
         if !output.status.success() {
             anyhow::bail!(
@@ -269,27 +674,16 @@ impl ProtonPass {
     }
 
     /// Update an item field (for saving generated public key)
-    pub fn update_item_field(
-        &self,
-        vault: &str,
-        title: &str,
-        field: &str,
-        value: &str,
-    ) -> Result<()> {
+    fn update_item_field(&self, vault: &str, title: &str, field: &str, value: &str) -> Result<()> {
         let field_arg = format!("{}={}", field, value);
-        let output = Command::new("pass-cli")
-            .args([
-                "item",
-                "update",
-                "--vault-name",
-                vault,
-                "--item-title",
-                title,
-                "--field",
-                &field_arg,
-            ])
-            .output()
-            .context("Failed to execute pass-cli item update")?;
+        let output = pass_cli(&[
+            "item",
+            "update",
+            &flag_arg("--vault-name", vault),
+            &flag_arg("--item-title", title),
+            &flag_arg("--field", &field_arg),
+        ])
+        .context("Failed to execute pass-cli item update")?;
 
         if !output.status.success() {
             anyhow::bail!(
@@ -302,47 +696,18 @@ impl ProtonPass {
         Ok(())
     }
 
-    /// Check if a vault exists by name
-    pub fn vault_exists(&self, name: &str) -> Result<bool> {
-        let vaults = self.list_vaults()?;
-        Ok(vaults.iter().any(|v| v == name))
-    }
-
     /// List all active item titles in a vault (any type)
-    pub fn list_item_titles(&self, vault: &str) -> Result<Vec<String>> {
-        let output = Command::new("pass-cli")
-            .args([
-                "item",
-                "list",
-                vault,
-                "--filter-state",
-                "active",
-                "--output",
-                "json",
-            ])
-            .output()
-            .context("Failed to execute pass-cli item list")?;
-
-        // Empty vault returns non-zero or empty output
-        if !output.status.success() || output.stdout.is_empty() {
-            return Ok(Vec::new());
-        }
-
-        let response: ItemListResponse =
-            serde_json::from_slice(&output.stdout).context("Failed to parse item list response")?;
-
-        Ok(response
-            .items
+    fn list_item_titles(&self, vault: &str) -> Result<Vec<String>> {
+        Ok(self
+            .list_items(vault, None)?
             .into_iter()
             .map(|item| item.content.title)
             .collect())
     }
 
     /// Create a new vault
-    pub fn create_vault(&self, name: &str) -> Result<()> {
-        let output = Command::new("pass-cli")
-            .args(["vault", "create", "--name", name])
-            .output()
+    fn create_vault(&self, name: &str) -> Result<()> {
+        let output = pass_cli(&["vault", "create", &flag_arg("--name", name)])
             .context("Failed to execute pass-cli vault create")?;
 
         if !output.status.success() {
@@ -357,7 +722,7 @@ impl ProtonPass {
     }
 
     /// Create a custom item for Teleport with SSH and Server Command fields
-    pub fn create_tsh_item(
+    fn create_tsh_item(
         &self,
         vault: &str,
         title: &str,
@@ -397,18 +762,15 @@ impl ProtonPass {
             .context("Failed to write template to temp file")?;
 
         // Create custom item from template
-        let output = Command::new("pass-cli")
-            .args([
-                "item",
-                "create",
-                "custom",
-                "--vault-name",
-                vault,
-                "--from-template",
-                temp_file.path().to_str().unwrap(),
-            ])
-            .output()
-            .context("Failed to create custom item")?;
+        let output = pass_cli(&[
+            "item",
+            "create",
+            "custom",
+            &flag_arg("--vault-name", vault),
+            "--from-template",
+            temp_file.path().to_str().unwrap(),
+        ])
+        .context("Failed to create custom item")?;
 
         if !output.status.success() {
             anyhow::bail!(
@@ -420,26 +782,220 @@ impl ProtonPass {
 
         Ok(())
     }
+}
 
-    fn get_field(fields: &[ExtraField], name: &str) -> Option<String> {
-        fields
+/// Merge Teleport items into `items` by title: an item that already exists
+/// gets the SSH/Server Command it lacks, others are added
+fn merge_teleport_items(items: &mut Vec<SshItem>, teleport_items: Vec<SshItem>) {
+    for teleport_item in teleport_items {
+        match items.iter_mut().find(|i| i.title == teleport_item.title) {
+            Some(existing) => {
+                if existing.ssh.is_none() {
+                    existing.ssh = teleport_item.ssh;
+                }
+                if existing.server_command.is_none() {
+                    existing.server_command = teleport_item.server_command;
+                }
+            }
+            None => items.push(teleport_item),
+        }
+    }
+}
+
+/// Items read from a pre-exported listing instead of pass-cli (`--from-json`):
+/// a JSON object mapping each vault name to its
+/// `pass-cli item list --filter-state active --output json` output.
+/// Writes to the items are refused; `pass://` references are still read with pass-cli.
+pub struct ExportedItems {
+    /// Converts items the same way as a live listing
+    pass: ProtonPass,
+    vaults: BTreeMap<String, Vec<Item>>,
+}
+
+impl ExportedItems {
+    /// Read an export from `path` ("-" reads stdin)
+    pub fn load(path: &Path, field_names: FieldNames) -> Result<Self> {
+        let stdin = path == Path::new("-");
+        let data = if stdin {
+            let mut data = Vec::new();
+            std::io::stdin()
+                .read_to_end(&mut data)
+                .context("Failed to read the item export from stdin")?;
+            data
+        } else {
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?
+        };
+
+        let vaults: BTreeMap<String, ItemListResponse> = serde_json::from_slice(&data)
+            .with_context(|| {
+                format!(
+                    "Failed to parse {} (expected an object of vault name -> pass-cli item list output)",
+                    if stdin { "stdin".into() } else { path.display().to_string() }
+                )
+            })?;
+
+        Ok(Self {
+            pass: ProtonPass::with_field_names(field_names),
+            vaults: vaults
+                .into_iter()
+                .map(|(vault, response)| (vault, response.items))
+                .collect(),
+        })
+    }
+
+    fn items(&self, vault: &str) -> Result<&[Item]> {
+        self.vaults
+            .get(vault)
+            .map(Vec::as_slice)
+            .with_context(|| format!("Vault '{}' is not in the item export", vault))
+    }
+
+    fn refuse(&self, action: String) -> Result<()> {
+        anyhow::bail!(
+            "Not {}: items come from a JSON export (--from-json)",
+            action
+        )
+    }
+}
+
+impl SecretBackend for ExportedItems {
+    fn name(&self) -> &'static str {
+        "JSON export"
+    }
+
+    fn list_all_vaults(&self) -> Result<Vec<String>> {
+        Ok(self.vaults.keys().cloned().collect())
+    }
+
+    /// SSH key and Teleport items, merged like a live listing. Keys stored as
+    /// attachments can't be downloaded offline, so they are left out.
+    fn list_all_items(&self, vault: &str, _include_attachments: bool) -> Result<Vec<SshItem>> {
+        let mut items: Vec<SshItem> = self
+            .items(vault)?
             .iter()
-            .find(|f| f.name == name)
-            .and_then(|f| f.content.text.clone())
-            .filter(|s| !s.is_empty())
+            .filter(|item| item.content.content.ssh_key.is_some())
+            .map(|item| self.pass.to_ssh_item(item.clone()))
+            .collect();
+        merge_teleport_items(&mut items, self.list_teleport_items(vault)?);
+        Ok(items)
     }
 
-    fn get_section_field(fields: &[SectionField], name: &str) -> Option<String> {
-        fields
+    fn list_teleport_items(&self, vault: &str) -> Result<Vec<SshItem>> {
+        Ok(self
+            .items(vault)?
             .iter()
-            .find(|f| f.name == name)
-            .and_then(|f| f.content.text.clone())
-            .filter(|s| !s.is_empty())
+            .filter(|item| item.content.content.custom.is_some())
+            .cloned()
+            .filter_map(ProtonPass::teleport_item)
+            .collect())
+    }
+
+    fn list_item_titles(&self, vault: &str) -> Result<Vec<String>> {
+        Ok(self
+            .items(vault)?
+            .iter()
+            .map(|item| item.content.title.clone())
+            .collect())
+    }
+
+    fn get_item_field(&self, path: &str) -> Result<String> {
+        self.pass.get_item_field(path)
+    }
+
+    fn update_item_field(&self, vault: &str, title: &str, field: &str, _value: &str) -> Result<()> {
+        self.refuse(format!(
+            "updating field '{}' of '{}/{}'",
+            field, vault, title
+        ))
+    }
+
+    fn create_vault(&self, name: &str) -> Result<()> {
+        self.refuse(format!("creating vault '{}'", name))
+    }
+
+    fn create_tsh_item(
+        &self,
+        vault: &str,
+        title: &str,
+        _ssh_command: &str,
+        _server_command: &str,
+    ) -> Result<()> {
+        self.refuse(format!("creating item '{}/{}'", vault, title))
     }
 }
 
+/// Whether a boolean-ish field value is set ("true", "yes", "1", "on"; case-insensitive)
+pub fn is_truthy(value: &str) -> bool {
+    matches!(
+        value.trim().to_lowercase().as_str(),
+        "true" | "yes" | "y" | "1" | "on"
+    )
+}
+
+/// Check if a vault is in the reserved/skip list (exact name, case-insensitive)
+pub fn is_skipped_vault(name: &str, skip: &[String]) -> bool {
+    skip.iter().any(|s| s.eq_ignore_ascii_case(name))
+}
+
 impl Default for ProtonPass {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A key-less item with the given extra text fields
+    fn item_with_fields(fields: &[(&str, &str)]) -> Item {
+        let extra_fields: Vec<_> = fields
+            .iter()
+            .map(|(name, value)| serde_json::json!({ "name": name, "content": { "Text": value } }))
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "content": {
+                "title": "web",
+                "content": { "Custom": {} },
+                "extra_fields": extra_fields,
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn is_truthy_accepts_common_true_values() {
+        for value in ["true", "TRUE", " yes ", "y", "1", "on", "On"] {
+            assert!(is_truthy(value), "{:?}", value);
+        }
+        for value in ["", "false", "no", "0", "off", "disabled", "2"] {
+            assert!(!is_truthy(value), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn disabled_field_marks_item_disabled() {
+        let pass = ProtonPass::new();
+        let disabled = [("Disabled", "yes"), ("pass-ssh-unpack: skip", "true")];
+        for field in disabled {
+            assert!(pass.to_ssh_item(item_with_fields(&[field])).disabled);
+        }
+        for field in [("Disabled", "no"), ("Disabled", "")] {
+            assert!(!pass.to_ssh_item(item_with_fields(&[field])).disabled);
+        }
+        assert!(!pass.to_ssh_item(item_with_fields(&[])).disabled);
+    }
+
+    #[test]
+    fn dash_prefixed_names_are_not_read_as_flags() {
+        assert_eq!(
+            flag_arg("--vault-name", "-Archive"),
+            "--vault-name=-Archive"
+        );
+
+        let args = item_list_args("-Archive", Some("ssh-key"), Some("-cursor"));
+        assert!(args.ends_with(&["--", "-Archive"]));
+        assert_eq!(args.iter().filter(|arg| **arg == "-Archive").count(), 1);
+        assert!(args.windows(2).any(|pair| pair == ["--cursor", "-cursor"]));
+    }
+}
@@ -1,13 +1,22 @@
 use anyhow::{Context, Result};
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-
-use crate::config::{Config, DEFAULT_RCLONE_PASSWORD_PATH};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::backend::SecretBackend;
+use crate::config::{self, Config};
+use crate::error::ExitCode;
+use crate::interactive;
+use crate::interrupt::{self, CriticalSection};
+use crate::json_lines;
 use crate::progress;
 use crate::proton_pass::ProtonPass;
+use crate::stats::Timings;
+use crate::tools;
 
 /// Entry for creating rclone remotes
 #[derive(Debug, Clone)]
@@ -15,10 +24,45 @@ pub struct RcloneEntry {
     pub remote_name: String,
     pub host: Option<String>,
     pub user: String,
+    pub port: Option<u16>,
     pub key_file: String,
+    /// Take the key from the SSH agent (`key_storage = "keychain"`); rclone
+    /// reads `<key_file>.pub` to pick it
+    pub key_use_agent: bool,
     pub other_aliases: String,
     pub ssh: Option<String>,
+    /// Jump host (fills `{proxy}` in ssh commands)
+    pub jump: Option<String>,
     pub server_command: Option<String>,
+    pub shell_type: Option<String>,
+    pub md5sum_command: Option<String>,
+    pub sha1sum_command: Option<String>,
+    /// Vault the item came from, recorded in the managed tag (None for Teleport nodes)
+    pub vault: Option<String>,
+}
+
+/// Description that tags a remote as managed by pass-ssh-unpack
+const MANAGED_DESCRIPTION: &str = "managed by pass-ssh-unpack";
+
+/// Warning for remotes kept because removing them would empty an encrypted config
+const EMPTY_ENCRYPTED_CONFIG_KEPT: &str = "encrypted rclone config not written: removing its last remotes would leave it empty. Still in the file (delete them with 'rclone config delete'):";
+
+/// Managed tag for a remote from this vault: `managed by pass-ssh-unpack [Vault]`
+fn managed_description(vault: Option<&str>) -> String {
+    match vault {
+        Some(vault) => format!("{} [{}]", MANAGED_DESCRIPTION, vault),
+        None => MANAGED_DESCRIPTION.to_string(),
+    }
+}
+
+/// Parse a managed tag. None if the remote is not managed; Some(None) for a
+/// tag without a vault (Teleport remotes, or written before vault tags).
+fn managed_vault(description: Option<&str>) -> Option<Option<&str>> {
+    let rest = description?.strip_prefix(MANAGED_DESCRIPTION)?;
+    if rest.is_empty() {
+        return Some(None);
+    }
+    rest.strip_prefix(" [")?.strip_suffix(']').map(Some)
 }
 
 /// In-memory rclone config that only writes to disk on finalize.
@@ -43,15 +87,163 @@ struct InMemoryConfig {
     finalized: bool,
 }
 
+/// The rclone config file as it was before `finalize` wrote it, so a failed
+/// write or re-encryption can put it back instead of leaving it plaintext.
+/// Kept as raw bytes, which also preserves an encrypted config as-is.
+struct ConfigSnapshot {
+    path: PathBuf,
+    /// File content (None if the file didn't exist yet)
+    content: Option<Vec<u8>>,
+}
+
+impl ConfigSnapshot {
+    fn take(path: &Path) -> Result<Self> {
+        let content =
+            if path.exists() {
+                Some(fs::read(path).with_context(|| {
+                    format!("Failed to snapshot rclone config: {}", path.display())
+                })?)
+            } else {
+                None
+            };
+        Ok(Self {
+            path: path.to_path_buf(),
+            content,
+        })
+    }
+
+    fn restore(&self) -> Result<()> {
+        forget_cached_config(&self.path);
+        match &self.content {
+            Some(content) => fs::write(&self.path, content),
+            None if self.path.exists() => fs::remove_file(&self.path),
+            None => Ok(()),
+        }
+        .with_context(|| format!("Failed to restore rclone config: {}", self.path.display()))
+    }
+}
+
+/// Pass through the result of writing the config; on failure, restore the
+/// snapshot taken before the write and say so in the error
+fn rollback_on_error(snapshot: &ConfigSnapshot, result: Result<()>) -> Result<()> {
+    let Err(error) = result else {
+        return Ok(());
+    };
+    match snapshot.restore() {
+        Ok(()) => Err(error.context("rclone config rolled back to its state before the run")),
+        Err(restore_error) => Err(error.context(format!(
+            "rclone config may be left unencrypted or partially written: {:#}",
+            restore_error
+        ))),
+    }
+}
+
+/// A decrypted rclone config, kept for the rest of the process
+#[derive(Clone)]
+struct CachedConfig {
+    content: String,
+    /// Password that decrypted it (reused to re-encrypt)
+    password: Option<String>,
+    /// Modification time and size of the file when it was read
+    stamp: Option<(SystemTime, u64)>,
+}
+
+/// Decrypted configs by path, so an interactive session that views the status
+/// and then exports decrypts each config once. Writing a config drops its entry.
+static DECRYPTED: Mutex<BTreeMap<PathBuf, CachedConfig>> = Mutex::new(BTreeMap::new());
+
+fn file_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// The cached decryption of `path`, unless the file changed since it was read
+fn cached_config(path: &Path) -> Option<CachedConfig> {
+    let cache = DECRYPTED.lock().unwrap_or_else(|e| e.into_inner());
+    cache
+        .get(path)
+        .filter(|cached| cached.stamp == file_stamp(path))
+        .cloned()
+}
+
+fn cache_config(path: &Path, content: &str, password: Option<&str>) {
+    let cached = CachedConfig {
+        content: content.to_string(),
+        password: password.map(str::to_string),
+        stamp: file_stamp(path),
+    };
+    DECRYPTED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(path.to_path_buf(), cached);
+}
+
+fn forget_cached_config(path: &Path) {
+    DECRYPTED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(path);
+}
+
+/// Check that `rclone config show` output for an encrypted config is a
+/// decrypted INI config with at least one remote section
+fn verify_decrypted(content: &str) -> Result<()> {
+    let wrong_password = "Wrong rclone password: the config did not decrypt";
+    let mut sections = 0;
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if line.contains("RCLONE_ENCRYPT_V") {
+            return Err(ExitCode::RcloneConfig.error(format!(
+                "{} (rclone returned the encrypted data)",
+                wrong_password
+            )));
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            sections += 1;
+        } else if !line.contains('=') {
+            return Err(ExitCode::RcloneConfig.error(format!(
+                "{} (output is not an rclone config)",
+                wrong_password
+            )));
+        }
+    }
+
+    // Empty configs are saved unencrypted, so an encrypted one should have remotes
+    if sections == 0 {
+        return Err(ExitCode::RcloneConfig.error(format!(
+            "{} (no remotes found). If the config really is empty, remove its encryption with `rclone config encryption remove`",
+            wrong_password
+        )));
+    }
+    Ok(())
+}
+
 impl InMemoryConfig {
-    /// Create a new in-memory config by decrypting the current rclone config.
-    /// The password must already be set in RCLONE_CONFIG_PASS if config is encrypted.
-    fn new(original_path: PathBuf, was_encrypted: bool, always_encrypt: bool) -> Result<Self> {
-        // Capture the password (if any)
-        let mut password = std::env::var("RCLONE_CONFIG_PASS").ok();
+    /// Create a new in-memory config by decrypting the current rclone config
+    /// (or reusing this process's earlier decryption of it).
+    /// Prompts for the password if the config is encrypted and none was given.
+    fn new(
+        original_path: PathBuf,
+        mut password: Option<String>,
+        was_encrypted: bool,
+        always_encrypt: bool,
+    ) -> Result<Self> {
+        if let Some(cached) = cached_config(&original_path) {
+            return Ok(Self {
+                content: cached.content,
+                original_path,
+                password: cached.password.or(password),
+                was_encrypted,
+                always_encrypt,
+                modified: false,
+                finalized: false,
+            });
+        }
 
         // Export decrypted config to memory
-        let mut output = Command::new("rclone")
+        let mut output = rclone_command(&original_path, password.as_deref())
             .args(["config", "show"])
             .output()
             .context("Failed to run rclone config show")?;
@@ -65,13 +257,13 @@ impl InMemoryConfig {
                     rpassword::read_password().context("Failed to read rclone password")?;
 
                 if pass_input.is_empty() {
-                    anyhow::bail!("No password provided for encrypted rclone config");
+                    return Err(ExitCode::RcloneConfig
+                        .error("No password provided for encrypted rclone config"));
                 }
 
-                std::env::set_var("RCLONE_CONFIG_PASS", &pass_input);
                 password = Some(pass_input);
 
-                output = Command::new("rclone")
+                output = rclone_command(&original_path, password.as_deref())
                     .args(["config", "show"])
                     .output()
                     .context("Failed to run rclone config show (retry)")?;
@@ -80,11 +272,21 @@ impl InMemoryConfig {
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            anyhow::bail!("Failed to decrypt rclone config: {}", stderr.trim());
+            return Err(ExitCode::RcloneConfig.error(format!(
+                "Failed to decrypt rclone config: {}",
+                stderr.trim()
+            )));
         }
 
         let content = String::from_utf8_lossy(&output.stdout).into_owned();
 
+        // Never edit (and later re-encrypt) content the password did not really
+        // decrypt: that would lock the config behind the wrong password
+        if was_encrypted {
+            verify_decrypted(&content)?;
+        }
+        cache_config(&original_path, &content, password.as_deref());
+
         Ok(Self {
             content,
             original_path,
@@ -111,9 +313,28 @@ impl InMemoryConfig {
     fn should_encrypt(&self) -> bool {
         // Always re-encrypt if: we have a password AND (was encrypted OR always_encrypt is set)
         // This ensures we never leave a previously-encrypted config unencrypted
+        self.wants_encryption() && !self.is_empty()
+    }
+
+    fn wants_encryption(&self) -> bool {
         self.password.is_some() && (self.was_encrypted || self.always_encrypt)
     }
 
+    /// Whether the config has no remote sections left
+    fn is_empty(&self) -> bool {
+        !self
+            .content
+            .lines()
+            .any(|line| line.trim_start().starts_with('['))
+    }
+
+    /// A config meant to be encrypted that has no remotes left is not written:
+    /// there is nothing to protect, an encrypted empty config trips up the next
+    /// run's detection, and writing it in plaintext would undo the encryption
+    fn skips_empty_encryption(&self) -> bool {
+        self.wants_encryption() && self.is_empty()
+    }
+
     /// Finalize: write config to disk and re-encrypt if needed.
     fn finalize(&mut self) -> Result<()> {
         if self.finalized {
@@ -121,27 +342,52 @@ impl InMemoryConfig {
         }
 
         if self.modified {
+            // Leave the file untouched rather than write an empty config in plaintext
+            if self.skips_empty_encryption() {
+                self.finalized = true;
+                return Ok(());
+            }
+
+            // The cached decryption no longer matches the file
+            forget_cached_config(&self.original_path);
+
             // Sort managed remotes alphabetically
             sort_managed_remotes(&mut self.content);
 
-            // Write decrypted content to the config file
-            fs::write(&self.original_path, &self.content)
-                .context("Failed to write rclone config")?;
-
-            // Re-encrypt if needed
-            if self.should_encrypt() {
-                if let Some(ref pass) = self.password {
-                    Self::encrypt_config(pass, &self.original_path)?;
-                }
-            }
+            // The plaintext write and the re-encryption are undone together on failure
+            let snapshot = ConfigSnapshot::take(&self.original_path)?;
+            rollback_on_error(&snapshot, self.write())?;
         }
 
         self.finalized = true;
         Ok(())
     }
 
+    /// Write the content to the config file, re-encrypting it if needed
+    fn write(&self) -> Result<()> {
+        fs::write(&self.original_path, &self.content).context("Failed to write rclone config")?;
+
+        if self.should_encrypt() {
+            if let Some(ref pass) = self.password {
+                Self::encrypt_config(pass, &self.original_path)?;
+            }
+        }
+        Ok(())
+    }
+
     /// Encrypt the rclone config with the given password.
     fn encrypt_config(password: &str, config_path: &std::path::Path) -> Result<()> {
+        // Refuse up front rather than leave the config plaintext after an obscure rclone error
+        if let Some(version) = tools::RCLONE.version() {
+            if version < tools::RCLONE.minimum {
+                anyhow::bail!(
+                    "rclone {} cannot encrypt the config (`rclone config encryption set` needs rclone {}+); upgrade rclone",
+                    version,
+                    tools::RCLONE.minimum
+                );
+            }
+        }
+
         // We need to pass the password to rclone. Using stdin would be ideal
         // but rclone config encryption set doesn't support it well.
         // Use a pipe on Unix or a temporary approach that minimizes exposure.
@@ -152,16 +398,8 @@ impl InMemoryConfig {
             use std::process::Stdio;
 
             // Use process substitution via bash to avoid temp files
-            let mut child = Command::new("rclone")
-                .args([
-                    "--config",
-                    config_path.to_str().unwrap_or_default(),
-                    "config",
-                    "encryption",
-                    "set",
-                    "--password-command",
-                    "cat",
-                ])
+            let mut child = rclone_command(config_path, None)
+                .args(["config", "encryption", "set", "--password-command", "cat"])
                 .stdin(Stdio::piped())
                 .stdout(Stdio::null())
                 .stderr(Stdio::piped())
@@ -185,10 +423,8 @@ impl InMemoryConfig {
         {
             // On Windows, we use echo via cmd - password briefly visible in process list
             // but no temp file on disk
-            let output = Command::new("rclone")
+            let output = rclone_command(config_path, None)
                 .args([
-                    "--config",
-                    config_path.to_str().unwrap_or_default(),
                     "config",
                     "encryption",
                     "set",
@@ -210,21 +446,103 @@ impl InMemoryConfig {
     }
 }
 
-/// Check if rclone config is encrypted by looking at the file content
-fn is_config_encrypted() -> bool {
-    let config_path = match get_config_path() {
-        Ok(p) => p,
-        Err(_) => return false,
+/// Build an rclone command pinned to a specific config file
+///
+/// The config password, if any, is handed to this child only via
+/// RCLONE_CONFIG_PASS - it is never set in our own process environment,
+/// so ssh-keygen, tsh and other children never see it.
+pub fn rclone_command(config_path: &Path, password: Option<&str>) -> Command {
+    let mut cmd = Command::new("rclone");
+    cmd.arg("--config").arg(config_path);
+    if let Some(password) = password {
+        cmd.env("RCLONE_CONFIG_PASS", password);
+    }
+    cmd
+}
+
+/// Where the password for an rclone config comes from
+pub enum PasswordSource {
+    /// RCLONE_CONFIG_PASS, already exported by the user
+    Environment(String),
+    /// A Proton Pass field (pass:// path)
+    PassPath(String),
+}
+
+/// Decide where the password for the rclone config at `config_path` comes
+/// from: its entry in `rclone.password_paths` if there is one, otherwise
+/// RCLONE_CONFIG_PASS if the user already exported it, otherwise the Proton
+/// Pass item at `password_path`
+pub fn password_source(config: &Config, config_path: &Path) -> Result<PasswordSource> {
+    if let Some(path) = config.rclone_password_path_for(config_path)? {
+        return Ok(PasswordSource::PassPath(path));
+    }
+    if let Ok(password) = std::env::var("RCLONE_CONFIG_PASS") {
+        return Ok(PasswordSource::Environment(password));
+    }
+    Ok(PasswordSource::PassPath(
+        config.expanded_rclone_password_path()?,
+    ))
+}
+
+/// Resolve the password for the rclone config at `config_path` (see `password_source`)
+pub fn load_password(config: &Config, config_path: &Path) -> Result<String> {
+    match password_source(config, config_path)? {
+        PasswordSource::Environment(password) => Ok(password),
+        PasswordSource::PassPath(path) => ProtonPass::new().get_item_field(&path),
+    }
+}
+
+/// Count the remotes managed by pass-ssh-unpack, without prompting for a
+/// password. None if the config can't be read (encrypted, with no or the wrong
+/// password). The decryption is cached for a later sync in the same process.
+pub fn count_managed_remotes(config_path: &Path, password: Option<&str>) -> Option<usize> {
+    let content = match cached_config(config_path) {
+        Some(cached) => cached.content,
+        None => {
+            let output = rclone_command(config_path, password)
+                .args(["config", "show"])
+                .env("RCLONE_ASK_PASSWORD", "false")
+                .output()
+                .ok()?;
+            if !output.status.success() {
+                return None;
+            }
+            let content = String::from_utf8_lossy(&output.stdout).into_owned();
+            if is_config_encrypted(config_path) {
+                verify_decrypted(&content).ok()?;
+            }
+            cache_config(config_path, &content, password);
+            content
+        }
     };
 
-    match fs::read_to_string(&config_path) {
+    let count = parse_ini_config(&content)
+        .values()
+        .filter(|remote| remote.is_managed())
+        .count();
+    Some(count)
+}
+
+/// Check if rclone config is encrypted by looking at the file content
+fn is_config_encrypted(config_path: &Path) -> bool {
+    match fs::read_to_string(config_path) {
         Ok(content) => content.contains("RCLONE_ENCRYPT_"),
         Err(_) => false,
     }
 }
 
-/// Get the rclone config file path
-fn get_config_path() -> Result<PathBuf> {
+/// Get the rclone config file path: `rclone.config_path` when set,
+/// otherwise ask rclone (which respects RCLONE_CONFIG)
+pub fn get_config_path(config: &Config) -> Result<PathBuf> {
+    if !config.rclone.config_path.is_empty() {
+        return config::expand_path(&config.rclone.config_path).with_context(|| {
+            format!(
+                "Invalid rclone.config_path \"{}\"",
+                config.rclone.config_path
+            )
+        });
+    }
+
     let output = Command::new("rclone")
         .args(["config", "file"])
         .output()
@@ -240,22 +558,191 @@ fn get_config_path() -> Result<PathBuf> {
     Ok(PathBuf::from(path))
 }
 
-/// Sync rclone SFTP remotes based on extracted SSH keys
+/// Build the desired remotes (primary SFTP remotes plus aliases) for the entries.
+/// Names get the configured prefix/suffix; managed remotes are still recognized
+/// by their description, so purge and full mode are unaffected by the naming.
+///
+/// An alias never replaces a primary remote or another entry's alias; such
+/// collisions are skipped and returned as messages (or fail with `strict_duplicates`).
+fn desired_remotes(
+    entries: &[RcloneEntry],
+    config: &Config,
+) -> Result<(HashMap<String, DesiredRemote>, Vec<String>)> {
+    let mut desired_remotes: HashMap<String, DesiredRemote> = HashMap::new();
+    let mut conflicts = Vec::new();
+
+    // Primary SFTP remotes first, so they win over aliases regardless of item order
+    for entry in entries.iter().filter(|e| !e.remote_name.is_empty()) {
+        // Routed items without their own command use the configured template
+        let template = &config.rclone.ssh_command_template;
+        let ssh = match entry.ssh {
+            Some(ref ssh) => Some(ssh.as_str()),
+            None if entry.jump.is_some() && !template.is_empty() => Some(template.as_str()),
+            None => None,
+        };
+        let ssh = ssh
+            .map(|command| expand_ssh_command(command, entry))
+            .transpose()
+            .with_context(|| format!("Invalid ssh command for remote '{}'", entry.remote_name))?;
+
+        desired_remotes.insert(
+            config.rclone_remote_name(&entry.remote_name),
+            DesiredRemote::Sftp {
+                host: entry.host.clone(),
+                user: entry.user.clone(),
+                port: entry.port,
+                key_file: if entry.key_file.is_empty() {
+                    None
+                } else {
+                    Some(entry.key_file.clone())
+                },
+                key_use_agent: entry.key_use_agent,
+                ssh,
+                server_command: entry.server_command.clone(),
+                shell_type: entry.shell_type.clone(),
+                md5sum_command: entry.md5sum_command.clone(),
+                sha1sum_command: entry.sha1sum_command.clone(),
+                vault: entry.vault.clone(),
+            },
+        );
+    }
+
+    // Alias remotes, only ever pointing at a primary remote built above
+    for entry in entries {
+        let remote_name = config.rclone_remote_name(&entry.remote_name);
+        let has_target = !entry.remote_name.is_empty()
+            && matches!(
+                desired_remotes.get(&remote_name),
+                Some(DesiredRemote::Sftp { .. })
+            );
+        for alias_name in entry
+            .other_aliases
+            .split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty() && *s != entry.remote_name)
+        {
+            let alias_remote = config.rclone_remote_name(alias_name);
+            if !has_target {
+                conflicts.push(format!(
+                    "alias remote '{}' has no primary remote to point at; alias skipped",
+                    alias_remote
+                ));
+                continue;
+            }
+            let conflict = match desired_remotes.get(&alias_remote) {
+                None => None,
+                Some(DesiredRemote::Alias { target, .. }) if *target == remote_name => continue,
+                Some(DesiredRemote::Alias { target, .. }) => Some(format!(
+                    "alias remote '{}' -> '{}' is already an alias of '{}'; alias skipped",
+                    alias_remote, remote_name, target
+                )),
+                Some(DesiredRemote::Sftp { .. }) => Some(format!(
+                    "alias remote '{}' -> '{}' collides with a primary remote; alias skipped",
+                    alias_remote, remote_name
+                )),
+            };
+            if let Some(message) = conflict {
+                if config.strict_duplicates {
+                    anyhow::bail!("{} (strict_duplicates)", message);
+                }
+                conflicts.push(message);
+                continue;
+            }
+            desired_remotes.insert(
+                alias_remote,
+                DesiredRemote::Alias {
+                    target: remote_name.clone(),
+                    vault: entry.vault.clone(),
+                },
+            );
+        }
+    }
+
+    Ok((desired_remotes, conflicts))
+}
+
+/// Fill `{host}`, `{user}` and `{proxy}` in an ssh command. Any other
+/// `{name}` is an error, as is a placeholder the entry has no value for.
+fn expand_ssh_command(command: &str, entry: &RcloneEntry) -> Result<String> {
+    let mut expanded = String::new();
+    let mut rest = command;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let name = after.find('}').map(|end| &after[..end]).filter(|name| {
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        });
+        let Some(name) = name else {
+            // Not a placeholder (e.g. a literal brace)
+            expanded.push('{');
+            rest = after;
+            continue;
+        };
+
+        let value = match name {
+            // Teleport nodes have no Host; their remote is named after the node
+            "host" => entry.host.as_deref().unwrap_or(&entry.remote_name),
+            "user" if !entry.user.is_empty() => &entry.user,
+            "user" => anyhow::bail!("{{user}} needs a Username"),
+            "proxy" => entry
+                .jump
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("{{proxy}} needs a Jump host"))?,
+            _ => anyhow::bail!(
+                "Unknown placeholder {{{}}} (expected {{host}}, {{user}} or {{proxy}})",
+                name
+            ),
+        };
+        expanded.push_str(value);
+        rest = &after[name.len() + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Render the managed remotes for the entries as rclone INI sections (sorted by
+/// name), without reading the real rclone config or needing rclone installed
+pub fn render_remotes(entries: &[RcloneEntry], config: &Config) -> Result<String> {
+    let (desired, _) = desired_remotes(entries, config)?;
+    let mut names: Vec<_> = desired.keys().collect();
+    names.sort();
+
+    let mut content = String::new();
+    for name in names {
+        create_remote_in_memory(&mut content, name, &desired[name]);
+    }
+    Ok(content)
+}
+
+/// Sync rclone SFTP remotes based on extracted SSH keys (None if rclone was skipped)
 pub fn sync_remotes(
     entries: &[RcloneEntry],
     config: &Config,
-    full_mode: bool,
-    dry_run: bool,
-    quiet: bool,
-) -> Result<()> {
+    options: &SyncOptions,
+    timings: &mut Timings,
+) -> Result<Option<SyncSummary>> {
+    let SyncOptions {
+        full: full_mode,
+        dry_run,
+        adopt,
+        quiet,
+        assume_yes,
+        vaults,
+    } = *options;
+    let mut summary = SyncSummary {
+        dry_run,
+        ..SyncSummary::default()
+    };
+
     // Skip if rclone not available
     if which::which("rclone").is_err() {
-        return Ok(());
+        return Ok(None);
     }
+    tools::RCLONE.warn_if_outdated();
 
     // Skip if no entries to process
     if entries.is_empty() {
-        return Ok(());
+        return Ok(None);
     }
 
     if !quiet {
@@ -263,117 +750,99 @@ pub fn sync_remotes(
         println!("Syncing rclone remotes...");
     }
 
-    // Set rclone password: check env first, then password_path -> env var
-    if std::env::var("RCLONE_CONFIG_PASS").is_err() {
-        let password_path = if config.rclone.password_path.is_empty() {
-            DEFAULT_RCLONE_PASSWORD_PATH
-        } else {
-            &config.rclone.password_path
-        };
-
-        let spinner = if !quiet {
-            Some(progress::spinner("Loading rclone password..."))
-        } else {
-            None
-        };
-
-        let proton_pass = ProtonPass::new();
-        match proton_pass.get_item_field(password_path) {
-            Ok(password) => {
-                std::env::set_var("RCLONE_CONFIG_PASS", password);
-                if let Some(sp) = spinner {
-                    sp.finish_and_clear();
-                }
+    // Load the password for the config file being managed
+    let original_config_path = get_config_path(config)?;
+    let spinner = if !quiet {
+        Some(progress::spinner("Loading rclone password..."))
+    } else {
+        None
+    };
+    let password = match timings.time("rclone password", || {
+        load_password(config, &original_config_path)
+    }) {
+        Ok(password) => {
+            if let Some(sp) = spinner {
+                sp.finish_and_clear();
             }
-            Err(_) => {
-                if let Some(sp) = spinner {
-                    sp.finish_with_message("failed");
-                }
-                if !quiet {
-                    println!("  (skipped - could not get rclone password)");
-                }
-                return Ok(());
+            password
+        }
+        Err(_) => {
+            if let Some(sp) = spinner {
+                sp.finish_with_message("failed");
+            }
+            if !quiet {
+                println!("  (skipped - could not get rclone password)");
             }
+            return Ok(None);
         }
-    }
+    };
 
-    // Determine if we should use in-memory config (encrypted or always_encrypt)
-    let was_encrypted = is_config_encrypted();
-    let _has_password = std::env::var("RCLONE_CONFIG_PASS").is_ok();
+    // The config is edited in memory and written once at the end, so a failed
+    // or interrupted run leaves the file as it was
+    let was_encrypted = is_config_encrypted(&original_config_path);
     let always_encrypt = config.rclone.always_encrypt && !dry_run;
-    // Always use in-memory config for reliable manipulation and sorting
-    let use_in_memory = true;
-    let original_config_path = get_config_path()?;
-
-    // Load config into memory
-    let mut in_memory_config = if use_in_memory {
-        let spinner_msg = if was_encrypted {
-            "Decrypting rclone config..."
-        } else {
-            "Reading rclone config..."
-        };
-        let spinner = if !quiet {
-            Some(progress::spinner(spinner_msg))
-        } else {
-            None
-        };
-        let cfg = InMemoryConfig::new(original_config_path.clone(), was_encrypted, always_encrypt)?;
-        if let Some(sp) = spinner {
-            sp.finish_and_clear();
-        }
-        Some(cfg)
+    let spinner_msg = if was_encrypted {
+        "Decrypting rclone config..."
     } else {
-        None
+        "Reading rclone config..."
     };
-
-    // Get current config - parse from memory or use rclone
-    let current_config = if let Some(ref cfg) = in_memory_config {
-        parse_ini_config(cfg.content())
+    let spinner = if !quiet {
+        Some(progress::spinner(spinner_msg))
     } else {
-        get_rclone_config(None)?
+        None
     };
+    let mut cfg = timings.time("rclone decrypt", || {
+        InMemoryConfig::new(
+            original_config_path.clone(),
+            Some(password.clone()),
+            was_encrypted,
+            always_encrypt,
+        )
+    })?;
+    if let Some(sp) = spinner {
+        sp.finish_and_clear();
+    }
+
+    let mut current_config = parse_ini_config(cfg.content());
 
     // Build list of desired remotes for comparison
-    let mut desired_remotes: HashMap<String, DesiredRemote> = HashMap::new();
-    for entry in entries {
-        if entry.remote_name.is_empty() {
-            continue;
+    let (desired_remotes, conflicts) = desired_remotes(entries, config)?;
+    if !quiet {
+        for message in &conflicts {
+            println!("  Warning: {}", message);
         }
-
-        // Primary SFTP remote
-        desired_remotes.insert(
-            entry.remote_name.clone(),
-            DesiredRemote::Sftp {
-                host: entry.host.clone(),
-                user: entry.user.clone(),
-                key_file: if entry.key_file.is_empty() {
-                    None
+    }
+    summary.warnings = conflicts;
+
+    // Adopt unmanaged remotes that point at a desired host+user, so they come
+    // back under management (renamed to the current scheme) instead of being skipped
+    let mut adopted: Vec<(String, String)> = Vec::new();
+    if adopt {
+        adopted = find_adoptable_remotes(&current_config, &desired_remotes);
+        for (old_name, new_name) in &adopted {
+            let description = managed_description(desired_remotes[new_name].vault());
+            adopt_remote_in_memory(cfg.content_mut(), old_name, new_name, &description);
+            json_lines::emit(
+                "rclone_op",
+                serde_json::json!({
+                    "op": "adopt",
+                    "remote": new_name,
+                    "from": old_name,
+                    "dry_run": dry_run,
+                }),
+            );
+            if !quiet {
+                if dry_run {
+                    println!("  Would adopt: {} -> {}", old_name, new_name);
                 } else {
-                    Some(entry.key_file.clone())
-                },
-                ssh: entry.ssh.clone(),
-                server_command: entry.server_command.clone(),
-            },
-        );
-
-        // Alias remotes
-        if !entry.other_aliases.is_empty() {
-            for alias_name in entry
-                .other_aliases
-                .split(',')
-                .map(|s| s.trim())
-                .filter(|s| !s.is_empty())
-            {
-                if alias_name != entry.remote_name {
-                    desired_remotes.insert(
-                        alias_name.to_string(),
-                        DesiredRemote::Alias {
-                            target: entry.remote_name.clone(),
-                        },
-                    );
+                    println!("  Adopted: {} -> {}", old_name, new_name);
                 }
             }
         }
+        current_config = parse_ini_config(cfg.content());
+        if !quiet && adopted.is_empty() {
+            println!("  No remotes to adopt.");
+        }
     }
 
     // Determine what needs to be done
@@ -387,11 +856,33 @@ pub fn sync_remotes(
     let mut desired_names: Vec<_> = desired_remotes.keys().collect();
     desired_names.sort();
 
+    let is_unmanaged = |name: &str| {
+        current_config
+            .get(name)
+            .is_some_and(|remote| !remote.is_managed())
+    };
+
     for name in desired_names {
         let desired = &desired_remotes[name];
+
+        // A skipped primary leaves its aliases pointing at someone else's remote
+        if let DesiredRemote::Alias { target, .. } = desired {
+            if is_unmanaged(target) && !is_unmanaged(name) {
+                let message = format!(
+                    "alias remote '{}' -> '{}' skipped: the target is an unmanaged remote",
+                    name, target
+                );
+                if !quiet {
+                    println!("  Warning: {}", message);
+                }
+                summary.warnings.push(message);
+                continue;
+            }
+        }
+
         if let Some(existing) = current_config.get(name) {
             // Check if it's managed by us
-            if existing.description.as_deref() != Some("managed by pass-ssh-unpack") {
+            if !existing.is_managed() {
                 skipped_unmanaged.push(name.clone());
                 continue;
             }
@@ -407,29 +898,48 @@ pub fn sync_remotes(
         }
     }
 
-    // In full mode, delete managed remotes that aren't in desired set
+    // In full mode, delete managed remotes that aren't in desired set. A run
+    // limited to some vaults leaves the remotes of other vaults alone.
     if full_mode {
         for (name, remote) in &current_config {
-            if remote.description.as_deref() == Some("managed by pass-ssh-unpack")
-                && !desired_remotes.contains_key(name)
-            {
+            if remote.in_vault_scope(vaults) && !desired_remotes.contains_key(name) {
                 to_delete.push(name.clone());
             }
         }
     }
 
+    // Without a terminal to ask on, deletions are skipped; the caller warns
+    to_delete.sort();
+    if !dry_run && !assume_yes && !to_delete.is_empty() {
+        if quiet || !interactive::is_interactive() {
+            summary.skipped_deletions = std::mem::take(&mut to_delete);
+        } else if !confirm_deletions(&to_delete) {
+            to_delete.clear();
+        }
+    }
+
     // Calculate totals for progress
     let total_ops = to_delete.len() + to_create.len() + to_update.len();
 
-    if total_ops == 0 {
+    if total_ops == 0 && (adopted.is_empty() || dry_run) {
         if !quiet {
             println!("  {} remotes up to date.", unchanged.len());
         }
-        return Ok(());
+        summary.unchanged = unchanged;
+        return Ok(Some(summary));
     }
 
     // For dry run, just show what would happen
     if dry_run {
+        for name in &to_delete {
+            emit_op("delete", name, true);
+        }
+        for (name, _) in &to_create {
+            emit_op("create", name, true);
+        }
+        for (name, _) in &to_update {
+            emit_op("update", name, true);
+        }
         if !quiet {
             for name in &to_delete {
                 println!("  Would delete: {}", name);
@@ -437,7 +947,7 @@ pub fn sync_remotes(
             for (name, desired) in &to_create {
                 match desired {
                     DesiredRemote::Sftp { .. } => println!("  Would create: {}", name),
-                    DesiredRemote::Alias { target } => {
+                    DesiredRemote::Alias { target, .. } => {
                         println!("  Would create alias: {} -> {}", name, target)
                     }
                 }
@@ -461,9 +971,17 @@ pub fn sync_remotes(
             }
             println!("  {}", parts.join(", "));
         }
-        return Ok(());
+
+        summary.created = to_create.into_iter().map(|(name, _)| name).collect();
+        summary.updated = to_update.into_iter().map(|(name, _)| name).collect();
+        summary.deleted = to_delete;
+        summary.unchanged = unchanged;
+        return Ok(Some(summary));
     }
 
+    // From here on, Ctrl-C lets the current step finish instead of exiting mid-edit
+    let _critical = CriticalSection::enter();
+
     // Show progress bar for operations
     let pb = if !quiet {
         Some(progress::rclone_progress_bar(total_ops as u64))
@@ -478,14 +996,14 @@ pub fn sync_remotes(
 
     // Delete remotes
     for name in &to_delete {
+        if interrupt::is_interrupted() {
+            break;
+        }
         if let Some(ref bar) = pb {
             bar.set_message(format!("Deleting: {}", name));
         }
-        if let Some(ref mut cfg) = in_memory_config {
-            delete_remote_in_memory(cfg.content_mut(), name);
-        } else {
-            delete_remote_via_rclone(name)?;
-        }
+        delete_remote_in_memory(cfg.content_mut(), name);
+        emit_op("delete", name, false);
         deleted_names.push(name.clone());
         completed += 1;
         if let Some(ref bar) = pb {
@@ -495,14 +1013,14 @@ pub fn sync_remotes(
 
     // Create new remotes
     for (name, desired) in &to_create {
+        if interrupt::is_interrupted() {
+            break;
+        }
         if let Some(ref bar) = pb {
             bar.set_message(format!("Creating: {}", name));
         }
-        if let Some(ref mut cfg) = in_memory_config {
-            create_remote_in_memory(cfg.content_mut(), name, desired);
-        } else {
-            create_remote_via_rclone(name, desired)?;
-        }
+        create_remote_in_memory(cfg.content_mut(), name, desired);
+        emit_op("create", name, false);
         created_names.push(name.clone());
         completed += 1;
         if let Some(ref bar) = pb {
@@ -512,16 +1030,15 @@ pub fn sync_remotes(
 
     // Update changed remotes
     for (name, desired) in &to_update {
+        if interrupt::is_interrupted() {
+            break;
+        }
         if let Some(ref bar) = pb {
             bar.set_message(format!("Updating: {}", name));
         }
-        if let Some(ref mut cfg) = in_memory_config {
-            delete_remote_in_memory(cfg.content_mut(), name);
-            create_remote_in_memory(cfg.content_mut(), name, desired);
-        } else {
-            delete_remote_via_rclone(name)?;
-            create_remote_via_rclone(name, desired)?;
-        }
+        delete_remote_in_memory(cfg.content_mut(), name);
+        create_remote_in_memory(cfg.content_mut(), name, desired);
+        emit_op("update", name, false);
         updated_names.push(name.clone());
         completed += 1;
         if let Some(ref bar) = pb {
@@ -533,44 +1050,55 @@ pub fn sync_remotes(
         bar.finish_and_clear();
     }
 
+    // Interrupted mid-edit: the in-memory config is simply not written (rollback)
+    if interrupt::is_interrupted() {
+        anyhow::bail!("Interrupted - rclone config left unchanged");
+    }
+
     // Finalize in-memory config (write to disk and re-encrypt)
-    if let Some(ref mut cfg) = in_memory_config {
-        let spinner_msg = if cfg.should_encrypt() {
-            "Encrypting rclone config..."
-        } else {
-            "Saving rclone config..."
-        };
-        let spinner = if !quiet {
-            Some(progress::spinner(spinner_msg))
-        } else {
-            None
-        };
-        cfg.finalize()?;
-        if let Some(sp) = spinner {
-            sp.finish_and_clear();
+    let spinner_msg = if cfg.should_encrypt() {
+        "Encrypting rclone config..."
+    } else {
+        "Saving rclone config..."
+    };
+    let spinner = if !quiet {
+        Some(progress::spinner(spinner_msg))
+    } else {
+        None
+    };
+    timings.time("rclone encrypt/save", || cfg.finalize())?;
+    if let Some(sp) = spinner {
+        sp.finish_and_clear();
+    }
+    // The write was skipped, so the remotes to delete are all still there
+    if cfg.skips_empty_encryption() {
+        let message = format!(
+            "{} {}",
+            EMPTY_ENCRYPTED_CONFIG_KEPT,
+            deleted_names.join(", ")
+        );
+        if !quiet {
+            println!("  Warning: {}", message);
         }
+        summary.warnings.push(message);
+        deleted_names.clear();
     }
 
+    created_names.sort();
+    updated_names.sort();
+    deleted_names.sort();
+
     // Summary
     if !quiet {
         // Show detailed lists of changes
-        if !created_names.is_empty() {
-            created_names.sort();
-            for name in &created_names {
-                println!("  + {}", name);
-            }
+        for name in &created_names {
+            println!("  + {}", name);
         }
-        if !updated_names.is_empty() {
-            updated_names.sort();
-            for name in &updated_names {
-                println!("  ~ {}", name);
-            }
+        for name in &updated_names {
+            println!("  ~ {}", name);
         }
-        if !deleted_names.is_empty() {
-            deleted_names.sort();
-            for name in &deleted_names {
-                println!("  - {}", name);
-            }
+        for name in &deleted_names {
+            println!("  - {}", name);
         }
 
         // Show counts summary
@@ -601,77 +1129,152 @@ pub fn sync_remotes(
         }
     }
 
-    Ok(())
+    summary.created = created_names;
+    summary.updated = updated_names;
+    summary.deleted = deleted_names;
+    summary.unchanged = unchanged;
+    Ok(Some(summary))
+}
+
+/// How `sync_remotes` applies the changes
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SyncOptions<'a> {
+    /// Delete managed remotes that are no longer desired
+    pub full: bool,
+    /// Show what would change without writing
+    pub dry_run: bool,
+    /// Rename matching unmanaged remotes into managed ones
+    pub adopt: bool,
+    pub quiet: bool,
+    /// Delete in full mode without asking
+    pub assume_yes: bool,
+    /// Vaults the run was limited to; full mode then only deletes remotes
+    /// tagged with one of them (None: every vault was processed)
+    pub vaults: Option<&'a [String]>,
+}
+
+/// Ask before `--full` deletes managed remotes a filter may have left out by mistake
+fn confirm_deletions(to_delete: &[String]) -> bool {
+    println!(
+        "  {} managed remote(s) are not in the selected items and would be deleted:",
+        to_delete.len()
+    );
+    for name in to_delete {
+        println!("    - {}", name);
+    }
+    let proceed = inquire::Confirm::new("Delete these remotes?")
+        .with_default(false)
+        .prompt()
+        .unwrap_or(false);
+    if !proceed {
+        println!("  Keeping them.");
+    }
+    proceed
+}
+
+/// Remotes changed by `sync_remotes` (what would change, for a dry run)
+#[derive(Debug, Clone, Default)]
+pub struct SyncSummary {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub deleted: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub dry_run: bool,
+    /// Aliases left out because of a conflict or a missing target
+    pub warnings: Vec<String>,
+    /// Remotes `--full` would have deleted, kept because nobody could confirm
+    pub skipped_deletions: Vec<String>,
 }
 
-/// Purge all managed rclone remotes
-pub fn purge_managed_remotes(config: &Config, dry_run: bool, quiet: bool) -> Result<()> {
+impl SyncSummary {
+    /// Human-readable summary, as printed at the end of a sync
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let (created, updated, deleted) = if self.dry_run {
+            ("to create", "to update", "to delete")
+        } else {
+            ("created", "updated", "deleted")
+        };
+        for (marker, names) in [
+            ("+", &self.created),
+            ("~", &self.updated),
+            ("-", &self.deleted),
+        ] {
+            for name in names {
+                out.push_str(&format!("  {} {}\n", marker, name));
+            }
+        }
+        out.push_str(&format!(
+            "  {} {}, {} {}, {} {}, {} unchanged\n",
+            self.created.len(),
+            created,
+            self.updated.len(),
+            updated,
+            self.deleted.len(),
+            deleted,
+            self.unchanged.len()
+        ));
+        out
+    }
+}
+
+/// Purge all managed rclone remotes (with `only`, just the managed remotes named in it;
+/// with `vaults`, only remotes tagged with one of them).
+/// Returns how many remotes were (or, in a dry run, would be) removed.
+pub fn purge_managed_remotes(
+    config: &Config,
+    only: Option<&HashSet<String>>,
+    vaults: Option<&[String]>,
+    dry_run: bool,
+    quiet: bool,
+) -> Result<usize> {
     // Skip if rclone not available
     if which::which("rclone").is_err() {
         if !quiet {
             println!("  (rclone not installed)");
         }
-        return Ok(());
+        return Ok(0);
     }
+    tools::RCLONE.warn_if_outdated();
 
-    // Set rclone password: check env first, then password_path -> env var
-    if std::env::var("RCLONE_CONFIG_PASS").is_err() {
-        let password_path = if config.rclone.password_path.is_empty() {
-            DEFAULT_RCLONE_PASSWORD_PATH
-        } else {
-            &config.rclone.password_path
-        };
-
-        let proton_pass = ProtonPass::new();
-        if let Ok(password) = proton_pass.get_item_field(password_path) {
-            std::env::set_var("RCLONE_CONFIG_PASS", password);
-        } else {
-            if !quiet {
-                println!("  (skipped rclone - could not get password)");
-            }
-            return Ok(());
+    // Load the password for the config file being managed
+    let original_config_path = get_config_path(config)?;
+    let Ok(password) = load_password(config, &original_config_path) else {
+        if !quiet {
+            println!("  (skipped rclone - could not get password)");
         }
-    }
+        return Ok(0);
+    };
 
-    // Determine if we should use in-memory config
-    let was_encrypted = is_config_encrypted();
-    let _has_password = std::env::var("RCLONE_CONFIG_PASS").is_ok();
+    // Edited in memory and written once at the end, as in sync_remotes
+    let was_encrypted = is_config_encrypted(&original_config_path);
     let always_encrypt = config.rclone.always_encrypt && !dry_run;
-    // Always use in-memory config for reliable manipulation
-    let use_in_memory = true;
-    let original_config_path = get_config_path()?;
-
-    // Load config into memory if needed (for reading current state)
-    let mut in_memory_config = if use_in_memory && !dry_run {
-        let spinner_msg = if was_encrypted {
-            "Decrypting rclone config..."
-        } else {
-            "Reading rclone config..."
-        };
-        let spinner = if !quiet {
-            Some(progress::spinner(spinner_msg))
-        } else {
-            None
-        };
-        let cfg = InMemoryConfig::new(original_config_path.clone(), was_encrypted, always_encrypt)?;
-        if let Some(sp) = spinner {
-            sp.finish_and_clear();
-        }
-        Some(cfg)
+    let spinner_msg = if was_encrypted {
+        "Decrypting rclone config..."
     } else {
-        None
+        "Reading rclone config..."
     };
-
-    // Get current config
-    let current_config = if let Some(ref cfg) = in_memory_config {
-        parse_ini_config(cfg.content())
+    let spinner = if !quiet {
+        Some(progress::spinner(spinner_msg))
     } else {
-        get_rclone_config(None)?
+        None
     };
+    let mut cfg = InMemoryConfig::new(
+        original_config_path.clone(),
+        Some(password.clone()),
+        was_encrypted,
+        always_encrypt,
+    )?;
+    if let Some(sp) = spinner {
+        sp.finish_and_clear();
+    }
+
+    let current_config = parse_ini_config(cfg.content());
 
     let managed_remotes: Vec<String> = current_config
         .iter()
-        .filter(|(_, remote)| remote.description.as_deref() == Some("managed by pass-ssh-unpack"))
+        .filter(|(_, remote)| remote.in_vault_scope(vaults))
+        .filter(|(name, _)| only.is_none_or(|names| names.contains(*name)))
         .map(|(name, _)| name.clone())
         .collect();
 
@@ -679,19 +1282,25 @@ pub fn purge_managed_remotes(config: &Config, dry_run: bool, quiet: bool) -> Res
         if !quiet {
             println!("  No managed rclone remotes found");
         }
-        return Ok(());
+        return Ok(0);
     }
 
     if dry_run {
+        for name in &managed_remotes {
+            emit_op("delete", name, true);
+        }
         if !quiet {
             for name in &managed_remotes {
                 println!("  Would remove: {}", name);
             }
             println!("  Would remove {} rclone remotes", managed_remotes.len());
         }
-        return Ok(());
+        return Ok(managed_remotes.len());
     }
 
+    // From here on, Ctrl-C lets the current step finish instead of exiting mid-edit
+    let _critical = CriticalSection::enter();
+
     let pb = if !quiet {
         Some(progress::rclone_progress_bar(managed_remotes.len() as u64))
     } else {
@@ -699,46 +1308,55 @@ pub fn purge_managed_remotes(config: &Config, dry_run: bool, quiet: bool) -> Res
     };
 
     for (i, name) in managed_remotes.iter().enumerate() {
+        if interrupt::is_interrupted() {
+            break;
+        }
         if let Some(ref bar) = pb {
             bar.set_message(format!("Deleting: {}", name));
             bar.set_position(i as u64 + 1);
         }
-        if let Some(ref mut cfg) = in_memory_config {
-            delete_remote_in_memory(cfg.content_mut(), name);
-        } else {
-            // This fallback shouldn't really be reached with use_in_memory=true always,
-            // but kept for safety if logic changes
-            delete_remote_via_rclone(name)?;
-        }
+        delete_remote_in_memory(cfg.content_mut(), name);
+        emit_op("delete", name, false);
     }
 
     if let Some(bar) = pb {
         bar.finish_and_clear();
     }
 
+    if interrupt::is_interrupted() {
+        anyhow::bail!("Interrupted - rclone config left unchanged");
+    }
+
     // Finalize in-memory config (write to disk and re-encrypt)
-    if let Some(ref mut cfg) = in_memory_config {
-        let spinner_msg = if cfg.should_encrypt() {
-            "Encrypting rclone config..."
-        } else {
-            "Saving rclone config..."
-        };
-        let spinner = if !quiet {
-            Some(progress::spinner(spinner_msg))
-        } else {
-            None
-        };
-        cfg.finalize()?;
-        if let Some(sp) = spinner {
-            sp.finish_and_clear();
-        }
+    let spinner_msg = if cfg.should_encrypt() {
+        "Encrypting rclone config..."
+    } else {
+        "Saving rclone config..."
+    };
+    let spinner = if !quiet {
+        Some(progress::spinner(spinner_msg))
+    } else {
+        None
+    };
+    cfg.finalize()?;
+    if let Some(sp) = spinner {
+        sp.finish_and_clear();
+    }
+    // The write was skipped, so nothing was actually removed
+    if cfg.skips_empty_encryption() {
+        eprintln!(
+            "  Warning: {} {}",
+            EMPTY_ENCRYPTED_CONFIG_KEPT,
+            managed_remotes.join(", ")
+        );
+        return Ok(0);
     }
 
     if !quiet {
         println!("  Removed {} rclone remotes", managed_remotes.len());
     }
 
-    Ok(())
+    Ok(managed_remotes.len())
 }
 
 #[derive(Debug, Clone)]
@@ -746,15 +1364,33 @@ enum DesiredRemote {
     Sftp {
         host: Option<String>,
         user: String,
+        port: Option<u16>,
         key_file: Option<String>,
+        key_use_agent: bool,
         ssh: Option<String>,
         server_command: Option<String>,
+        shell_type: Option<String>,
+        md5sum_command: Option<String>,
+        sha1sum_command: Option<String>,
+        vault: Option<String>,
     },
     Alias {
         target: String,
+        vault: Option<String>,
     },
 }
 
+impl DesiredRemote {
+    /// Vault the remote comes from, for its managed tag
+    fn vault(&self) -> Option<&str> {
+        match self {
+            DesiredRemote::Sftp { vault, .. } | DesiredRemote::Alias { vault, .. } => {
+                vault.as_deref()
+            }
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct RcloneRemote {
     #[serde(rename = "type")]
@@ -764,35 +1400,79 @@ struct RcloneRemote {
     #[serde(default)]
     key_file: Option<String>,
     #[serde(default)]
+    key_use_agent: Option<String>,
+    #[serde(default)]
     remote: Option<String>,
     #[serde(default)]
     host: Option<String>,
     #[serde(default)]
     user: Option<String>,
     #[serde(default)]
+    port: Option<String>,
+    #[serde(default)]
     ssh: Option<String>,
     #[serde(default)]
     server_command: Option<String>,
+    #[serde(default)]
+    shell_type: Option<String>,
+    #[serde(default)]
+    md5sum_command: Option<String>,
+    #[serde(default)]
+    sha1sum_command: Option<String>,
 }
 
-/// Check if existing remote matches desired config
+impl RcloneRemote {
+    /// Tagged as managed, with or without a vault
+    fn is_managed(&self) -> bool {
+        managed_vault(self.description.as_deref()).is_some()
+    }
+
+    /// Managed and, when the run is limited to `vaults`, tagged with one of them.
+    /// Untagged remotes (from before vault tags) only count for unlimited runs.
+    fn in_vault_scope(&self, vaults: Option<&[String]>) -> bool {
+        match (vaults, managed_vault(self.description.as_deref())) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(vaults), Some(Some(vault))) => vaults.iter().any(|v| v == vault),
+            (Some(_), Some(None)) => false,
+        }
+    }
+}
+
+/// Check if existing remote matches desired config. A managed tag without the
+/// desired vault counts as a change, so older remotes are retagged on update.
 fn remote_matches(existing: &RcloneRemote, desired: &DesiredRemote) -> bool {
+    let description = managed_description(desired.vault());
+    if existing.description.as_deref() != Some(description.as_str()) {
+        return false;
+    }
     match desired {
         DesiredRemote::Sftp {
             host,
             user,
+            port,
             key_file,
+            key_use_agent,
             ssh,
             server_command,
+            shell_type,
+            md5sum_command,
+            sha1sum_command,
+            vault: _,
         } => {
             existing.remote_type == "sftp"
                 && existing.host.as_deref() == host.as_deref()
                 && existing.user.as_deref() == Some(user.as_str())
+                && existing.port.as_deref() == port.map(|p| p.to_string()).as_deref()
                 && existing.key_file.as_deref() == key_file.as_deref()
+                && existing.key_use_agent.as_deref() == key_use_agent.then_some("true")
                 && existing.ssh.as_deref() == ssh.as_deref()
                 && existing.server_command.as_deref() == server_command.as_deref()
+                && existing.shell_type.as_deref() == shell_type.as_deref()
+                && existing.md5sum_command.as_deref() == md5sum_command.as_deref()
+                && existing.sha1sum_command.as_deref() == sha1sum_command.as_deref()
         }
-        DesiredRemote::Alias { target } => {
+        DesiredRemote::Alias { target, .. } => {
             existing.remote_type == "alias"
                 && existing
                     .remote
@@ -803,26 +1483,116 @@ fn remote_matches(existing: &RcloneRemote, desired: &DesiredRemote) -> bool {
     }
 }
 
+/// Pair unmanaged SFTP remotes with the desired remote for the same host+user.
+/// A remote already named like the desired one is preferred; otherwise the
+/// match must be unambiguous. Returns (existing name, desired name) pairs.
+fn find_adoptable_remotes(
+    current: &HashMap<String, RcloneRemote>,
+    desired: &HashMap<String, DesiredRemote>,
+) -> Vec<(String, String)> {
+    let is_unmanaged_sftp =
+        |remote: &RcloneRemote| remote.remote_type == "sftp" && !remote.is_managed();
+
+    let mut desired_names: Vec<_> = desired.keys().collect();
+    desired_names.sort();
+
+    let mut adopted: Vec<(String, String)> = Vec::new();
+    for name in desired_names {
+        let DesiredRemote::Sftp {
+            host: Some(host),
+            user,
+            ..
+        } = &desired[name]
+        else {
+            continue;
+        };
+        let matches = |remote: &RcloneRemote| {
+            is_unmanaged_sftp(remote)
+                && remote.host.as_deref() == Some(host.as_str())
+                && remote.user.as_deref() == Some(user.as_str())
+        };
+
+        if let Some(existing) = current.get(name) {
+            // Same name: adopt in place if it is ours in all but the tag
+            if matches(existing) {
+                adopted.push((name.clone(), name.clone()));
+            }
+            continue;
+        }
+
+        let candidates: Vec<&String> = current
+            .iter()
+            .filter(|(n, r)| matches(r) && !adopted.iter().any(|(old, _)| old == *n))
+            .map(|(n, _)| n)
+            .collect();
+        if let [only] = candidates.as_slice() {
+            adopted.push(((*only).clone(), name.clone()));
+        }
+    }
+    adopted
+}
+
+/// Rename a remote section and tag it as managed
+fn adopt_remote_in_memory(content: &mut String, old_name: &str, new_name: &str, description: &str) {
+    let old_header = format!("[{}]", old_name);
+    let eol = line_ending(content);
+    let mut result = String::new();
+    let mut in_section = false;
+
+    for line in content.split_inclusive('\n') {
+        let text = line.trim_end_matches(['\r', '\n']);
+        if text.starts_with('[') {
+            in_section = text == old_header;
+            if in_section {
+                result.push_str(&format!(
+                    "[{}]{}description = {}{}",
+                    new_name, eol, description, eol
+                ));
+                continue;
+            }
+        }
+        if in_section && text.trim_start().starts_with("description") {
+            continue;
+        }
+        result.push_str(line);
+    }
+
+    *content = result;
+}
+
 fn create_remote_in_memory(content: &mut String, name: &str, desired: &DesiredRemote) {
     // Remove existing section if present
     *content = remove_ini_section(content, name);
+    let description = managed_description(desired.vault());
 
     // Build new section
     let section = match desired {
         DesiredRemote::Sftp {
             host,
             user,
+            port,
             key_file,
+            key_use_agent,
             ssh,
             server_command,
+            shell_type,
+            md5sum_command,
+            sha1sum_command,
+            vault: _,
         } => {
             let mut s = format!("[{}]\ntype = sftp\n", name);
             if let Some(h) = host {
                 s.push_str(&format!("host = {}\n", h));
             }
             s.push_str(&format!("user = {}\n", user));
+            if let Some(p) = port {
+                s.push_str(&format!("port = {}\n", p));
+            }
             if let Some(kf) = key_file {
                 s.push_str(&format!("key_file = {}\n", kf));
+                if *key_use_agent {
+                    s.push_str("key_use_agent = true\n");
+                }
             } else if host.is_some() {
                 // Only ask for password if connecting to a host directly
                 s.push_str("ask_password = true\n");
@@ -833,182 +1603,85 @@ fn create_remote_in_memory(content: &mut String, name: &str, desired: &DesiredRe
             if let Some(cmd) = server_command {
                 s.push_str(&format!("server_command = {}\n", cmd));
             }
-            s.push_str("description = managed by pass-ssh-unpack\n");
+            if let Some(shell) = shell_type {
+                s.push_str(&format!("shell_type = {}\n", shell));
+            }
+            if let Some(cmd) = md5sum_command {
+                s.push_str(&format!("md5sum_command = {}\n", cmd));
+            }
+            if let Some(cmd) = sha1sum_command {
+                s.push_str(&format!("sha1sum_command = {}\n", cmd));
+            }
+            s.push_str(&format!("description = {}\n", description));
             s
         }
-        DesiredRemote::Alias { target } => {
+        DesiredRemote::Alias { target, .. } => {
             format!(
-                "[{}]\ntype = alias\nremote = {}:\ndescription = managed by pass-ssh-unpack\n",
-                name, target
+                "[{}]\ntype = alias\nremote = {}:\ndescription = {}\n",
+                name, target, description
             )
         }
     };
 
-    // Append new section
+    // Append new section, matching the file's line endings
+    let eol = line_ending(content);
     if !content.is_empty() && !content.ends_with('\n') {
-        content.push('\n');
+        content.push_str(eol);
     }
-    content.push_str(&section);
+    content.push_str(&section.replace('\n', eol));
 }
 
-fn create_remote_via_rclone(name: &str, desired: &DesiredRemote) -> Result<()> {
-    let mut cmd = Command::new("rclone");
-
-    match desired {
-        DesiredRemote::Sftp {
-            host,
-            user,
-            key_file,
-            ssh,
-            server_command,
-        } => {
-            cmd.args(["config", "create", name, "sftp"]);
-            if let Some(h) = host {
-                cmd.arg(format!("host={}", h));
-            }
-            cmd.arg(format!("user={}", user));
-
-            if let Some(kf) = key_file {
-                cmd.arg(format!("key_file={}", kf));
-            } else if host.is_some() {
-                // Only ask for password if connecting to a host directly
-                cmd.arg("ask_password=true");
-            }
-
-            if let Some(ssh_cmd) = ssh {
-                cmd.arg(format!("ssh={}", ssh_cmd));
-            }
-
-            if let Some(srv_cmd) = server_command {
-                cmd.arg(format!("server_command={}", srv_cmd));
-            }
-
-            cmd.arg("description=managed by pass-ssh-unpack");
-        }
-        DesiredRemote::Alias { target } => {
-            cmd.args([
-                "config",
-                "create",
-                name,
-                "alias",
-                &format!("remote={}:", target),
-                "description=managed by pass-ssh-unpack",
-            ]);
-        }
-    }
-
-    cmd.output().context("Failed to create rclone remote")?;
-    Ok(())
+/// Record a remote change for `--json-lines`
+fn emit_op(op: &str, name: &str, dry_run: bool) {
+    json_lines::emit(
+        "rclone_op",
+        serde_json::json!({ "op": op, "remote": name, "dry_run": dry_run }),
+    );
 }
 
 fn delete_remote_in_memory(content: &mut String, name: &str) {
     *content = remove_ini_section(content, name);
 }
 
-fn delete_remote_via_rclone(name: &str) -> Result<()> {
-    Command::new("rclone")
-        .args(["config", "delete", name])
-        .output()
-        .context("Failed to delete rclone remote")?;
-    Ok(())
+/// Line ending used by an INI file: CRLF if any line has one, LF otherwise
+fn line_ending(content: &str) -> &'static str {
+    if content.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    }
 }
 
-/// Remove an INI section by name from content
+/// Remove an INI section by name from content.
+/// Remaining lines keep their original endings. The section's own trailing
+/// blank lines go with it, so the gap before the next section is the previous
+/// one's separator; removing the last section drops that separator too.
 fn remove_ini_section(content: &str, section_name: &str) -> String {
     let section_header = format!("[{}]", section_name);
     let mut result = String::new();
     let mut skip = false;
+    let mut removed = false;
 
-    for line in content.lines() {
-        if line.starts_with('[') {
-            skip = line == section_header;
+    for line in content.split_inclusive('\n') {
+        let text = line.trim_end_matches(['\r', '\n']);
+        if text.starts_with('[') {
+            skip = text == section_header;
+            removed |= skip;
         }
         if !skip {
             result.push_str(line);
-            result.push('\n');
         }
     }
 
-    result
-}
-
-fn get_rclone_config(config_path: Option<&PathBuf>) -> Result<HashMap<String, RcloneRemote>> {
-    let mut cmd = Command::new("rclone");
-
-    if let Some(path) = config_path {
-        cmd.arg("--config").arg(path);
-    }
-
-    cmd.args(["config", "dump"]);
-    cmd.env("RCLONE_ASK_PASSWORD", "false");
-
-    let output = cmd.output().context("Failed to run rclone config dump")?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-
-        // Check if this is an encrypted config without password
-        if stderr.contains("unable to decrypt configuration")
-            || stderr.contains("RCLONE_CONFIG_PASS")
-        {
-            // Prompt user for password
-            eprint!("Rclone config password: ");
-            let password = rpassword::read_password().context("Failed to read rclone password")?;
-
-            if password.is_empty() {
-                anyhow::bail!(
-                    "No password provided. Set 'password_path' in your config file under [rclone] to avoid this prompt, e.g.:\n\
-                     \n\
-                     [rclone]\n\
-                     password_path = \"pass://Personal/rclone/password\""
-                );
-            }
-
-            // Set the password and retry
-            std::env::set_var("RCLONE_CONFIG_PASS", &password);
-
-            let mut retry_cmd = Command::new("rclone");
-            if let Some(path) = config_path {
-                retry_cmd.arg("--config").arg(path);
-            }
-            retry_cmd.args(["config", "dump"]);
-
-            let retry_output = retry_cmd
-                .output()
-                .context("Failed to run rclone config dump")?;
-
-            if !retry_output.status.success() {
-                let retry_stderr = String::from_utf8_lossy(&retry_output.stderr);
-                if retry_stderr.contains("wrong password")
-                    || retry_stderr.contains("unable to decrypt")
-                {
-                    std::env::remove_var("RCLONE_CONFIG_PASS");
-                    anyhow::bail!("Incorrect rclone config password");
-                }
-                return Ok(HashMap::new());
-            }
-
-            if retry_output.stdout.is_empty() {
-                return Ok(HashMap::new());
-            }
-
-            let config: HashMap<String, RcloneRemote> =
-                serde_json::from_slice(&retry_output.stdout).unwrap_or_default();
-
-            return Ok(config);
+    // A removed trailing section leaves the previous one's separator behind
+    if removed {
+        let eol = line_ending(content);
+        while result.ends_with(&eol.repeat(2)) {
+            result.truncate(result.len() - eol.len());
         }
-
-        return Ok(HashMap::new());
-    }
-
-    if output.stdout.is_empty() {
-        return Ok(HashMap::new());
     }
 
-    let config: HashMap<String, RcloneRemote> =
-        serde_json::from_slice(&output.stdout).unwrap_or_default();
-
-    Ok(config)
+    result
 }
 
 /// Parse rclone INI config content into a HashMap of remotes
@@ -1055,11 +1728,16 @@ fn fields_to_remote(fields: &HashMap<String, String>) -> Option<RcloneRemote> {
         remote_type,
         description: fields.get("description").cloned(),
         key_file: fields.get("key_file").cloned(),
+        key_use_agent: fields.get("key_use_agent").cloned(),
         remote: fields.get("remote").cloned(),
         host: fields.get("host").cloned(),
         user: fields.get("user").cloned(),
+        port: fields.get("port").cloned(),
         ssh: fields.get("ssh").cloned(),
         server_command: fields.get("server_command").cloned(),
+        shell_type: fields.get("shell_type").cloned(),
+        md5sum_command: fields.get("md5sum_command").cloned(),
+        sha1sum_command: fields.get("sha1sum_command").cloned(),
     })
 }
 
@@ -1073,6 +1751,7 @@ fn fields_to_remote(fields: &HashMap<String, String>) -> Option<RcloneRemote> {
 /// 3. Sort "managed" by section name.
 /// 4. Reconstruct content: unmanaged first, then managed.
 fn sort_managed_remotes(content: &mut String) {
+    let eol = line_ending(content);
     let mut sections: Vec<(String, String, bool)> = Vec::new(); // (name, full_text, is_managed)
     let mut current_section_name: Option<String> = None;
     let mut current_section_lines: Vec<String> = Vec::new();
@@ -1081,12 +1760,12 @@ fn sort_managed_remotes(content: &mut String) {
     // Helper to push the current accumulated section
     let mut push_section = |name: Option<String>, lines: Vec<String>, managed: bool| {
         if let Some(n) = name {
-            let text = lines.join("\n");
+            let text = lines.join(eol);
             sections.push((n, text, managed));
         } else if !lines.is_empty() {
             // Content before the first section (e.g. comments at top of file)
             // We treat this as an "unmanaged" block with empty name
-            let text = lines.join("\n");
+            let text = lines.join(eol);
             sections.push((String::new(), text, false));
         }
     };
@@ -1134,24 +1813,164 @@ fn sort_managed_remotes(content: &mut String) {
 
     for (_, text, _) in unmanaged {
         content.push_str(&text);
-        content.push('\n');
+        content.push_str(eol);
     }
 
     for (_, text, _) in managed {
         // Ensure there's a blank line before each section if not at very start
         if !content.is_empty() && !content.ends_with("\n\n") && !content.ends_with('\n') {
-            content.push('\n');
-            // content.push('\n'); // Optional: force blank line between sections
+            content.push_str(eol);
+            // content.push_str(eol); // Optional: force blank line between sections
         }
         content.push_str(&text);
-        content.push('\n');
+        content.push_str(eol);
     }
 
     // Clean up multiple newlines at end
-    while content.ends_with("\n\n") {
-        content.pop();
+    let blank_tail = eol.repeat(2);
+    while content.ends_with(&blank_tail) {
+        content.truncate(content.len() - eol.len());
     }
     if !content.ends_with('\n') && !content.is_empty() {
-        content.push('\n');
+        content.push_str(eol);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn in_memory(path: &Path, content: &str) -> InMemoryConfig {
+        InMemoryConfig {
+            content: content.to_string(),
+            original_path: path.to_path_buf(),
+            password: Some("secret".to_string()),
+            was_encrypted: true,
+            always_encrypt: true,
+            modified: false,
+            finalized: false,
+        }
+    }
+
+    fn entry(remote_name: &str, other_aliases: &str) -> RcloneEntry {
+        RcloneEntry {
+            remote_name: remote_name.to_string(),
+            host: Some(format!("{}.example", remote_name)),
+            user: "root".to_string(),
+            port: None,
+            key_file: String::new(),
+            key_use_agent: false,
+            other_aliases: other_aliases.to_string(),
+            ssh: None,
+            jump: None,
+            server_command: None,
+            shell_type: None,
+            md5sum_command: None,
+            sha1sum_command: None,
+            vault: Some("Work".to_string()),
+        }
+    }
+
+    #[test]
+    fn finalize_leaves_file_untouched_when_no_remotes_remain() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rclone.conf");
+        let on_disk = "RCLONE_ENCRYPT_V0:\nencrypted-content\n";
+        fs::write(&path, on_disk).unwrap();
+
+        let mut cfg = in_memory(
+            &path,
+            "[only]\ntype = sftp\ndescription = managed by pass-ssh-unpack\n",
+        );
+        delete_remote_in_memory(cfg.content_mut(), "only");
+        assert!(cfg.skips_empty_encryption());
+        assert!(!cfg.should_encrypt());
+
+        cfg.finalize().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), on_disk);
+    }
+
+    #[test]
+    fn aliases_of_a_skipped_primary_are_not_created() {
+        let config = Config::default();
+        let entries = [entry("", "orphan, stray"), entry("web", "www")];
+
+        let (desired, conflicts) = desired_remotes(&entries, &config).unwrap();
+
+        assert!(!desired.contains_key(&config.rclone_remote_name("orphan")));
+        assert!(!desired.contains_key(&config.rclone_remote_name("stray")));
+        assert!(matches!(
+            desired.get(&config.rclone_remote_name("www")),
+            Some(DesiredRemote::Alias { target, .. }) if *target == config.rclone_remote_name("web")
+        ));
+        assert_eq!(desired.len(), 2);
+        assert_eq!(conflicts.len(), 2);
+        assert!(conflicts
+            .iter()
+            .all(|c| c.contains("has no primary remote to point at")));
+    }
+
+    #[test]
+    fn remove_ini_section_keeps_crlf_line_endings() {
+        let content = "[a]\r\ntype = sftp\r\n\r\n[b]\r\ntype = alias\r\n\r\n[c]\r\ntype = sftp\r\n";
+
+        assert_eq!(
+            remove_ini_section(content, "b"),
+            "[a]\r\ntype = sftp\r\n\r\n[c]\r\ntype = sftp\r\n"
+        );
+        assert_eq!(
+            remove_ini_section(content, "c"),
+            "[a]\r\ntype = sftp\r\n\r\n[b]\r\ntype = alias\r\n"
+        );
+    }
+
+    #[test]
+    fn remove_ini_section_takes_its_trailing_blank_lines_along() {
+        let content = "[a]\ntype = sftp\n\n\n[b]\ntype = alias\n\n\n[c]\ntype = sftp\n";
+
+        let removed = remove_ini_section(content, "b");
+        assert_eq!(removed, "[a]\ntype = sftp\n\n\n[c]\ntype = sftp\n");
+        // Only the previous section's separator is left, so repeated runs don't stack blank lines
+        assert_eq!(remove_ini_section(&removed, "b"), removed);
+
+        assert_eq!(
+            remove_ini_section(content, "c"),
+            "[a]\ntype = sftp\n\n\n[b]\ntype = alias\n"
+        );
+        assert_eq!(
+            remove_ini_section(content, "a"),
+            "[b]\ntype = alias\n\n\n[c]\ntype = sftp\n"
+        );
+        // The removed section's blank lines don't add to the separator before it
+        assert_eq!(
+            remove_ini_section("[a]\nx = 1\n\n[b]\ny = 2\n\n\n\n[c]\n", "b"),
+            "[a]\nx = 1\n\n[c]\n"
+        );
+    }
+
+    #[test]
+    fn adopt_remote_in_memory_renames_and_tags_the_section() {
+        let mut content =
+            "[a]\ntype = sftp\n\n[old]\ntype = sftp\ndescription = mine\nhost = h\n".to_string();
+
+        adopt_remote_in_memory(&mut content, "old", "new", MANAGED_DESCRIPTION);
+
+        assert_eq!(
+            content,
+            "[a]\ntype = sftp\n\n[new]\ndescription = managed by pass-ssh-unpack\ntype = sftp\nhost = h\n"
+        );
+    }
+
+    #[test]
+    fn adopt_remote_in_memory_keeps_crlf_line_endings() {
+        let mut content =
+            "[a]\r\ntype = sftp\r\n\r\n[old]\r\ntype = sftp\r\nhost = h\r\n".to_string();
+
+        adopt_remote_in_memory(&mut content, "old", "new", MANAGED_DESCRIPTION);
+
+        assert_eq!(
+            content,
+            "[a]\r\ntype = sftp\r\n\r\n[new]\r\ndescription = managed by pass-ssh-unpack\r\ntype = sftp\r\nhost = h\r\n"
+        );
     }
 }
@@ -0,0 +1,2142 @@
+use anyhow::{Context, Result};
+use indicatif::MultiProgress;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::ansible;
+use crate::backend::{self, BackendKind, SecretBackend};
+use crate::config::{self, Config, FieldNames, LabelMatch};
+use crate::error::{ErrorCollector, ExitCode};
+use crate::interactive;
+use crate::interrupt;
+use crate::json_lines;
+use crate::lock::InstanceLock;
+use crate::platform;
+use crate::progress;
+use crate::proton_pass::{self, SshItem};
+use crate::rclone::{self, RcloneEntry, SyncOptions, SyncSummary};
+use crate::ssh::{self, sanitize_name, SshManager, VerifySsh};
+use crate::stats::Timings;
+use crate::teleport::{Node, OsFamily, Teleport, DEFAULT_SFTP_SERVER};
+use crate::tools;
+
+/// Options for an export (or purge) run
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// Vault patterns to process (empty uses `default_vaults` from config)
+    pub vaults: Vec<String>,
+    /// Item title patterns to process (empty uses `default_items` from config)
+    pub items: Vec<String>,
+    /// Proton Pass labels to process (combined per `label_match`)
+    pub labels: Vec<String>,
+    /// Full regeneration (clear config first)
+    pub full: bool,
+    /// Skip items whose key file already exists
+    pub only_new: bool,
+    /// Never write private keys to disk
+    pub no_write_keys: bool,
+    /// Load the key files written by this run into the SSH agent
+    pub add_to_agent: bool,
+    /// Check the generated hosts with ssh after writing the config
+    pub verify_ssh: Option<VerifySsh>,
+    /// Only process SSH keys (skip rclone sync)
+    pub ssh_only: bool,
+    /// Only process rclone remotes (skip SSH key extraction)
+    pub rclone_only: bool,
+    /// Remove all managed SSH keys and rclone remotes instead of exporting
+    pub purge: bool,
+    /// Show what would be done without making changes
+    pub dry_run: bool,
+    /// Print a unified diff of the SSH config this run would write
+    pub diff_ssh_config: bool,
+    /// Password manager to read items from
+    pub backend: BackendKind,
+    /// Read items from this JSON export instead of the backend ("-" for stdin)
+    pub from_json: Option<PathBuf>,
+    /// Also write an Ansible inventory of the processed hosts here
+    pub ansible_inventory: Option<PathBuf>,
+    /// Render the managed rclone remotes instead of syncing them
+    pub export_rclone_config: bool,
+    /// File for the rendered rclone remotes (None prints them to stdout)
+    pub rclone_export_path: Option<PathBuf>,
+    /// Bring unmanaged rclone remotes for the same host+user under management
+    pub adopt_remotes: bool,
+    /// Proceed without asking when more than `max_items` items match, and
+    /// back up and replace an SSH config this tool did not write
+    pub assume_yes: bool,
+    /// End the run at the first error instead of collecting errors
+    pub fail_fast: bool,
+    /// Suppress output
+    pub quiet: bool,
+}
+
+/// Structured result of an export run
+#[derive(Default)]
+pub struct RunReport {
+    /// Number of primary hosts in the generated SSH config
+    pub hosts: usize,
+    /// Number of alias hosts in the generated SSH config
+    pub aliases: usize,
+    /// Number of keys left as-is because their content had not changed
+    pub unchanged_keys: usize,
+    /// Number of key files (re)written
+    pub written_keys: usize,
+    /// Number of derived public keys saved back to their items
+    pub public_keys_saved: usize,
+    /// Whether the SSH config content changed
+    pub ssh_config_changed: bool,
+    /// Number of hosts left out because `no_write_keys` gave them no key file
+    pub skipped_no_key_file: usize,
+    /// Path of the SSH config that was written (None if SSH was skipped)
+    pub ssh_config_path: Option<PathBuf>,
+    /// rclone entries built from the processed items
+    pub rclone_entries: Vec<RcloneEntry>,
+    /// Remotes changed by the rclone sync (None if it was skipped or failed)
+    pub rclone_sync: Option<SyncSummary>,
+    /// Non-fatal errors collected while processing
+    pub errors: ErrorCollector,
+    /// Durations of the major phases of the run
+    pub timings: Timings,
+}
+
+impl RunReport {
+    /// Human-readable end-of-run summary (written by `--report-file`)
+    pub fn render(&self, dry_run: bool) -> String {
+        let mut out = self.render_counts(dry_run);
+        out.push_str(&self.errors.render());
+        out
+    }
+
+    /// The SSH and rclone part of `render`, without warnings and errors
+    /// (printed by `--summary-only`, which reports those separately)
+    pub fn render_counts(&self, dry_run: bool) -> String {
+        let mut out = String::new();
+        if dry_run {
+            out.push_str("[DRY RUN] No changes were made\n");
+        }
+        if let Some(ref path) = self.ssh_config_path {
+            out.push_str(&format!(
+                "SSH config: {}\n  {} hosts, {} aliases, {} keys unchanged\n",
+                path.display(),
+                self.hosts,
+                self.aliases,
+                self.unchanged_keys
+            ));
+            if self.skipped_no_key_file > 0 {
+                out.push_str(&format!(
+                    "  {} host(s) skipped (--no-write-keys)\n",
+                    self.skipped_no_key_file
+                ));
+            }
+            if self.public_keys_saved > 0 {
+                out.push_str(&format!(
+                    "  {} public key(s) saved back\n",
+                    self.public_keys_saved
+                ));
+            }
+        }
+        if let Some(ref sync) = self.rclone_sync {
+            out.push_str("rclone remotes:\n");
+            out.push_str(&sync.render());
+        }
+        out
+    }
+
+    /// One-line summary of what changed (used by `--watch` after each cycle)
+    pub fn change_summary(&self) -> String {
+        let mut parts = Vec::new();
+        if self.ssh_config_changed {
+            parts.push(format!(
+                "SSH config updated ({} hosts, {} aliases)",
+                self.hosts, self.aliases
+            ));
+        }
+        if self.written_keys > 0 {
+            parts.push(format!("{} key(s) written", self.written_keys));
+        }
+        if self.public_keys_saved > 0 {
+            parts.push(format!("{} public key(s) saved", self.public_keys_saved));
+        }
+        if let Some(ref sync) = self.rclone_sync {
+            for (count, action) in [
+                (sync.created.len(), "created"),
+                (sync.updated.len(), "updated"),
+                (sync.deleted.len(), "deleted"),
+            ] {
+                if count > 0 {
+                    parts.push(format!("{} rclone remote(s) {}", count, action));
+                }
+            }
+        }
+        if parts.is_empty() {
+            parts.push("No changes".to_string());
+        }
+
+        let errors = self.errors.errors().len();
+        if errors > 0 {
+            parts.push(format!("{} error(s)", errors));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Options for importing Teleport nodes
+#[derive(Debug, Clone, Default)]
+pub struct TshImportOptions {
+    /// Target vault for imported items (the fallback vault with `vault_label`)
+    pub vault: String,
+    /// Route each node into a vault named after its value for this Teleport label
+    pub vault_label: Option<String>,
+    /// Node name patterns to import (empty imports all)
+    pub items: Vec<String>,
+    /// Match `items` ignoring case (`case_insensitive`)
+    pub case_insensitive: bool,
+    /// Skip scanning remote servers for sftp-server path (use default)
+    pub no_scan: bool,
+    /// How many nodes to scan at once (`--parallel-tsh-scan`)
+    pub parallel_scan: usize,
+    /// Use this OS family's sftp-server path instead of scanning each node
+    pub assume_os: Option<OsFamily>,
+    /// Password manager to create items in
+    pub backend: BackendKind,
+    /// Teleport (leaf) cluster to import nodes from
+    pub cluster: Option<String>,
+    /// Proxy address for the stored tsh commands instead of the one `tsh status` reports
+    pub proxy: Option<String>,
+    /// Create rclone remotes directly instead of Proton Pass items
+    pub direct_rclone: bool,
+    /// Update SSH/Server Command fields of existing items when they differ
+    pub update_existing: bool,
+    /// Show what would be done without making changes
+    pub dry_run: bool,
+    /// Suppress output
+    pub quiet: bool,
+}
+
+/// Options for `--uninstall`
+#[derive(Debug, Clone, Default)]
+pub struct UninstallOptions {
+    /// Also delete this config file
+    pub remove_config: Option<PathBuf>,
+    /// Show what would be removed without removing it
+    pub dry_run: bool,
+    /// Suppress output
+    pub quiet: bool,
+    /// Skip the confirmation
+    pub assume_yes: bool,
+}
+
+/// Counts from a Teleport import
+#[derive(Debug, Clone, Default)]
+pub struct TshImportReport {
+    pub created: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    /// Nodes left out because they lack the `vault_label` label (and no fallback vault is set)
+    pub warnings: Vec<String>,
+}
+
+/// Extract SSH keys and sync rclone remotes for the given config and options
+pub fn run_export(config: &Config, options: &ExportOptions) -> Result<RunReport> {
+    let mut report = RunReport {
+        errors: ErrorCollector::new().with_fail_fast(options.fail_fast),
+        ..RunReport::default()
+    };
+    let dry_run = options.dry_run;
+
+    // Determine which operations to run
+    // --ssh: only SSH, --rclone: only rclone, neither: both
+    let do_ssh = !options.rclone_only; // SSH unless --rclone only
+                                       // rclone unless --ssh only (exporting the rclone config always needs it)
+    let do_rclone = options.export_rclone_config || (!options.ssh_only && config.rclone.enabled);
+
+    // Helper for logging
+    let log = |msg: &str| {
+        if !options.quiet {
+            println!("{}", msg);
+        }
+    };
+
+    if dry_run {
+        log("[DRY RUN] No changes will be made");
+        log("");
+    }
+
+    // Hold the instance lock for the whole run so two processes never race on
+    // the SSH or rclone config (dry runs don't write anything)
+    let _lock = if dry_run {
+        None
+    } else {
+        Some(InstanceLock::acquire()?)
+    };
+
+    // Unsupported backends fail before anything else happens
+    let mut backend = match options.from_json {
+        Some(ref path) => backend::from_json(path, config.field_names.clone())?,
+        None => backend::create(options.backend, config.field_names.clone())?,
+    };
+    if config.read_only {
+        backend = backend::read_only(backend);
+    }
+
+    // Check dependencies (a JSON export needs no pass-cli)
+    report.timings.time("Dependency check", || {
+        check_dependencies(options.from_json.is_none())
+    })?;
+
+    // A dry run can't tell whether the real run would get the rclone password,
+    // so look it up (read-only) now rather than fail halfway through later
+    if dry_run && do_rclone && !options.export_rclone_config && !options.purge {
+        check_rclone_password(backend.as_ref(), config, &mut report, &log);
+    }
+
+    // Handle purge mode
+    if options.purge {
+        if options.vaults.is_empty() && options.items.is_empty() && options.labels.is_empty() {
+            handle_purge(config, dry_run, options.quiet, do_ssh, do_rclone)?;
+        } else {
+            handle_filtered_purge(backend.as_ref(), config, options, do_ssh, do_rclone)?;
+        }
+        return Ok(report);
+    }
+
+    if do_ssh {
+        log(&format!("Extracting SSH keys from {}...", backend.name()));
+    } else {
+        log("Syncing rclone remotes only...");
+    }
+    log("");
+
+    // Names this machine answers to in machine-specific titles
+    let machine_names = machine_names(config);
+
+    // Setup SSH manager
+    let ssh_output_dir = config.expanded_ssh_output_dir()?;
+    if do_ssh && !config.allow_synced_dir {
+        if let Some(folder) = config::synced_folder(&ssh_output_dir) {
+            report.errors.add_warning(format!(
+                "ssh_output_dir {} is inside the cloud-synced folder '{}', so private keys may be uploaded; consider a local directory (--allow-synced-dir silences this)",
+                ssh_output_dir.display(),
+                folder
+            ));
+        }
+    }
+    let mut ssh_manager = SshManager::new(
+        &ssh_output_dir,
+        options.full,
+        dry_run,
+        options.assume_yes,
+        config,
+    )?
+    .with_only_new(options.only_new)
+    .with_no_write_keys(options.no_write_keys);
+
+    // Get vaults to process
+    let spinner = if !options.quiet {
+        Some(progress::spinner("Loading vaults..."))
+    } else {
+        None
+    };
+    let all_vaults = report
+        .timings
+        .time("Vault listing", || backend.list_all_vaults())?;
+    if let Some(sp) = spinner {
+        sp.finish_and_clear();
+    }
+
+    // Apply vault filters (CLI overrides config defaults)
+    let vault_patterns = if options.vaults.is_empty() {
+        &config.default_vaults
+    } else {
+        &options.vaults
+    };
+
+    // A literal --vault that names no existing vault is almost always a typo
+    let missing: Vec<&String> = options
+        .vaults
+        .iter()
+        .filter(|pattern| {
+            is_literal_pattern(pattern)
+                && !all_vaults
+                    .iter()
+                    .any(|v| matches_pattern(v, pattern, config.case_insensitive))
+        })
+        .collect();
+    if !missing.is_empty() {
+        let available = filter_vaults(
+            &all_vaults,
+            &[],
+            &config.skip_vaults,
+            config.case_insensitive,
+        );
+        return Err(ExitCode::Usage.error(format!(
+            "Vault not found: {}\n  Available vaults: {}",
+            missing
+                .iter()
+                .map(|v| format!("'{}'", v))
+                .collect::<Vec<_>>()
+                .join(", "),
+            if available.is_empty() {
+                "(none)".to_string()
+            } else {
+                available.join(", ")
+            }
+        )));
+    }
+
+    let vaults_to_process = filter_vaults(
+        &all_vaults,
+        vault_patterns,
+        &config.skip_vaults,
+        config.case_insensitive,
+    );
+
+    if vaults_to_process.is_empty() && !vault_patterns.is_empty() {
+        log("Warning: No vaults matched the specified patterns");
+    }
+
+    // Apply item filters (CLI overrides config defaults)
+    let item_patterns = if options.items.is_empty() {
+        &config.default_items
+    } else {
+        &options.items
+    };
+
+    // Collect rclone entries for later sync
+    let mut rclone_entries: Vec<RcloneEntry> = Vec::new();
+
+    // From here on, Ctrl-C stops the loops and keeps what was processed so far
+    let _cancellable = interrupt::CancellableSection::enter();
+
+    // Process each vault with progress bar (if doing SSH or rclone)
+    if do_ssh || do_rclone {
+        // Fetch and filter every vault first, so the item count (and the
+        // max_items cap) reflects what would actually be processed
+        let spinner = if !options.quiet && !vaults_to_process.is_empty() {
+            Some(progress::spinner("Fetching items..."))
+        } else {
+            None
+        };
+        let mut selections: Vec<(&String, Option<VaultSelection>)> = Vec::new();
+        for vault in &vaults_to_process {
+            if interrupt::is_interrupted() {
+                break;
+            }
+            match report.timings.time("Item fetch", || {
+                backend.list_all_items(vault, config.include_attachments)
+            }) {
+                Ok(items) => selections.push((
+                    vault,
+                    Some(select_items(
+                        items,
+                        item_patterns,
+                        &options.labels,
+                        config.label_match,
+                        config.case_insensitive,
+                        do_rclone,
+                        &machine_names,
+                    )),
+                )),
+                Err(e) => {
+                    report
+                        .errors
+                        .add(&format!("Failed to list items in vault '{}'", vault), e)?;
+                    selections.push((vault, None));
+                }
+            }
+        }
+        if let Some(sp) = spinner {
+            sp.finish_and_clear();
+        }
+        proton_pass::ensure_session()?;
+        for warning in backend.take_warnings() {
+            report.errors.add_warning(warning);
+        }
+
+        let unlabeled: usize = selections
+            .iter()
+            .filter_map(|(_, selection)| selection.as_ref())
+            .map(|selection| selection.unlabeled)
+            .sum();
+        if unlabeled > 0 {
+            report.errors.add_warning(format!(
+                "pass-cli reported no labels for {} item(s); --label was not applied to them",
+                unlabeled
+            ));
+        }
+
+        let total_items: usize = selections
+            .iter()
+            .filter_map(|(_, selection)| selection.as_ref())
+            .map(|selection| selection.items.len())
+            .sum();
+        if config.max_items > 0 && total_items > config.max_items {
+            confirm_item_count(total_items, config.max_items, options)?;
+        }
+
+        // Vault bar plus a per-vault item bar underneath it
+        let multi = if !options.quiet && !vaults_to_process.is_empty() {
+            Some(MultiProgress::new())
+        } else {
+            None
+        };
+        let vault_pb = multi
+            .as_ref()
+            .map(|m| m.add(progress::vault_progress_bar(vaults_to_process.len() as u64)));
+
+        // Helper for logging that works with the progress bars
+        let pb_log = |msg: &str| {
+            if !options.quiet {
+                if let Some(ref m) = multi {
+                    m.println(msg).ok();
+                } else {
+                    println!("{}", msg);
+                }
+            }
+        };
+
+        for (i, (vault, selection)) in selections.into_iter().enumerate() {
+            if interrupt::is_interrupted() {
+                break;
+            }
+            pb_log(&format!("[{}]", vault));
+
+            let Some(selection) = selection else {
+                pb_log("  (error listing items)");
+                pb_log("");
+                if let Some(ref pb) = vault_pb {
+                    pb.set_position(i as u64 + 1);
+                }
+                continue;
+            };
+
+            if selection.fetched == 0 {
+                pb_log("  (no items)");
+                pb_log("");
+                if let Some(ref pb) = vault_pb {
+                    pb.set_position(i as u64 + 1);
+                }
+                continue;
+            }
+
+            for note in &selection.skipped {
+                pb_log(note);
+            }
+
+            let item_pb = multi
+                .as_ref()
+                .map(|m| m.add(progress::item_progress_bar(selection.items.len() as u64)));
+
+            for (j, item) in selection.items.into_iter().enumerate() {
+                if interrupt::is_interrupted() {
+                    break;
+                }
+                if let Some(ref pb) = item_pb {
+                    pb.set_position(j as u64 + 1);
+                    pb.set_message(item.title.clone());
+                }
+
+                proton_pass::ensure_session()?;
+                pb_log(&format!("  Processing: {}", item.title));
+
+                // Extract and process the SSH key
+                let result = report.timings.time("Key processing", || {
+                    ssh_manager.process_item(backend.as_ref(), vault, &item, &pb_log)
+                });
+                for warning in ssh_manager.take_warnings() {
+                    report.errors.add_warning(warning);
+                }
+                json_lines::emit(
+                    "item",
+                    serde_json::json!({
+                        "vault": vault,
+                        "title": item.title,
+                        "status": match result {
+                            Ok(Some(_)) => "processed",
+                            Ok(None) => "skipped",
+                            Err(_) => "failed",
+                        },
+                    }),
+                );
+                match result {
+                    Ok(entry) => {
+                        if let Some(rclone_entry) = entry {
+                            rclone_entries.push(rclone_entry);
+                        }
+                    }
+                    Err(e) => {
+                        report
+                            .errors
+                            .add(&format!("Failed to process '{}'", item.title), e)?;
+                    }
+                }
+            }
+
+            if let (Some(m), Some(pb)) = (&multi, item_pb) {
+                pb.finish_and_clear();
+                m.remove(&pb);
+            }
+
+            pb_log("");
+            if let Some(ref pb) = vault_pb {
+                pb.set_position(i as u64 + 1);
+            }
+        }
+
+        if let Some(pb) = vault_pb {
+            pb.finish_and_clear();
+        }
+
+        // ssh-keygen time is a part of key processing
+        report
+            .timings
+            .add("  ssh-keygen", ssh_manager.keygen_time());
+
+        for warning in ssh_manager.take_rejected_key_warnings() {
+            report.errors.add_warning(warning);
+        }
+
+        for (title, e) in ssh_manager.resolve_alias_items(&log) {
+            report
+                .errors
+                .add(&format!("Failed to process '{}'", title), e)?;
+        }
+
+        // Public keys go back in one parallel batch rather than one call per item inline
+        let pending = ssh_manager.pending_public_keys();
+        if pending > 0 && config.read_only {
+            report.errors.add_warning(format!(
+                "{} public key(s) not saved to {} (read-only mode)",
+                pending,
+                backend.name()
+            ));
+        } else if pending > 0 && !interrupt::is_interrupted() {
+            let spinner = if !options.quiet {
+                Some(progress::spinner(&format!(
+                    "Saving {} public key(s) to {}...",
+                    pending,
+                    backend.name()
+                )))
+            } else {
+                None
+            };
+            let (saved, failures) = report.timings.time("Public key sync", || {
+                ssh_manager.sync_public_keys(backend.as_ref())
+            });
+            if let Some(sp) = spinner {
+                sp.finish_and_clear();
+            }
+            report.public_keys_saved = saved;
+            log(&format!(
+                "Saved {} of {} public key(s) to {}.",
+                saved,
+                pending,
+                backend.name()
+            ));
+            log("");
+            for (title, e) in failures {
+                report
+                    .errors
+                    .add(&format!("Failed to save the public key of '{}'", title), e)?;
+            }
+        }
+
+        if let Some(ref path) = options.ansible_inventory {
+            let content = ansible::render(path, ssh_manager.inventory());
+            if dry_run {
+                log(&format!(
+                    "Would write Ansible inventory to {}:",
+                    path.display()
+                ));
+                log(content.trim_end());
+            } else {
+                std::fs::write(path, &content)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+                log(&format!("Ansible inventory written to: {}", path.display()));
+            }
+            log("");
+        }
+
+        // Generate SSH config (only if doing SSH)
+        if do_ssh {
+            log("Generating SSH config...");
+            report.ssh_config_changed = ssh_manager.config_changed();
+            let (primary_count, alias_count) = report
+                .timings
+                .time("SSH config write", || ssh_manager.write_config())?;
+            report.hosts = primary_count;
+            report.aliases = alias_count;
+            report.unchanged_keys = ssh_manager.unchanged_keys();
+            report.written_keys = ssh_manager.written_keys();
+            report.skipped_no_key_file = ssh_manager.skipped_no_key_file();
+            report.ssh_config_path = Some(ssh_manager.config_path().to_path_buf());
+
+            // Printed even with --quiet: the diff is what was asked for
+            if options.diff_ssh_config {
+                let diff = ssh_manager.config_diff()?;
+                if diff.is_empty() {
+                    println!("SSH config is up to date (no changes).");
+                } else {
+                    print!("{}", diff);
+                }
+            }
+
+            log("");
+            log(&format!(
+                "Done! Generated config has {} hosts and {} aliases ({} keys unchanged).",
+                primary_count, alias_count, report.unchanged_keys
+            ));
+            if dry_run {
+                let (create, overwrite) = ssh_manager.planned_keys();
+                log(&format!(
+                    "Key files: {} to create, {} to overwrite, {} unchanged.",
+                    create, overwrite, report.unchanged_keys
+                ));
+            }
+            if report.skipped_no_key_file > 0 {
+                log(&format!(
+                    "{} host(s) skipped: no IdentityAgent and keys are not written (--no-write-keys).",
+                    report.skipped_no_key_file
+                ));
+            }
+            log(&format!(
+                "SSH config written to: {}",
+                ssh_manager.config_path().display()
+            ));
+        }
+
+        if options.add_to_agent && !dry_run && !ssh_manager.written_key_paths().is_empty() {
+            log("");
+            log("Adding new keys to the SSH agent...");
+            let extra_args = platform::ssh_add_args();
+            for path in ssh_manager.written_key_paths() {
+                match ssh::add_to_agent(path, &extra_args) {
+                    Ok(true) => log(&format!("  + {}", path.display())),
+                    Ok(false) => {
+                        report.errors.add_warning(
+                            "No SSH agent is running (check SSH_AUTH_SOCK); new keys were not added"
+                                .to_string(),
+                        );
+                        break;
+                    }
+                    Err(e) => {
+                        log(&format!("  ! {} (not added)", path.display()));
+                        report
+                            .errors
+                            .add_warning(format!("ssh-add {}: {:#}", path.display(), e));
+                    }
+                }
+            }
+        }
+
+        if let Some(mode) = options.verify_ssh.filter(|_| !dry_run) {
+            log("");
+            let spinner = (!options.quiet).then(|| progress::spinner("Verifying SSH hosts..."));
+            let checks = report
+                .timings
+                .time("SSH verify", || ssh_manager.verify_hosts(mode));
+            if let Some(sp) = spinner {
+                sp.finish_and_clear();
+            }
+            log("Verifying SSH hosts...");
+
+            // A syntax error makes ssh reject the whole file, so every host
+            // fails the same way; report that once
+            let first_error = checks
+                .first()
+                .and_then(|c| c.config.as_ref().err())
+                .map(|e| format!("{:#}", e));
+            let whole_file = first_error.filter(|message| {
+                checks.iter().all(|c| {
+                    c.config
+                        .as_ref()
+                        .is_err_and(|e| format!("{:#}", e) == *message)
+                })
+            });
+            if let Some(ref message) = whole_file {
+                log("  ! config rejected for every host");
+                report.errors.add(
+                    &format!("ssh -G (all {} hosts)", checks.len()),
+                    anyhow::anyhow!("{}", message),
+                )?;
+            }
+
+            let mut failed = 0;
+            for check in checks {
+                match (check.config, check.connect) {
+                    (Err(_), _) if whole_file.is_some() => failed += 1,
+                    (Err(e), _) => {
+                        failed += 1;
+                        log(&format!("  ! {} (config rejected)", check.host));
+                        report.errors.add(&format!("ssh -G {}", check.host), e)?;
+                    }
+                    (Ok(()), Some(Err(e))) => {
+                        failed += 1;
+                        log(&format!("  ! {} (unreachable)", check.host));
+                        report
+                            .errors
+                            .add_warning(format!("{} is unreachable: {:#}", check.host, e));
+                    }
+                    (Ok(()), Some(Ok(()))) => log(&format!("  + {} (reachable)", check.host)),
+                    (Ok(()), None) => log(&format!("  + {}", check.host)),
+                }
+            }
+            if failed == 0 {
+                log("All hosts passed.");
+            }
+        }
+    }
+
+    // Partial results are written above; syncing rclone from them could delete
+    // or rewrite remotes of items that were never reached
+    if interrupt::is_interrupted() {
+        report.errors.add(
+            "Interrupted",
+            anyhow::anyhow!(if do_rclone {
+                "remaining items were not processed and rclone was not synced"
+            } else {
+                "remaining items were not processed"
+            }),
+        )?;
+        report.rclone_entries = rclone_entries;
+        return Ok(report);
+    }
+
+    // Render rclone remotes instead of syncing them
+    if options.export_rclone_config {
+        let content = rclone::render_remotes(&rclone_entries, config)?;
+        match options.rclone_export_path {
+            Some(ref path) => {
+                std::fs::write(path, &content)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+                log(&format!("rclone config written to: {}", path.display()));
+            }
+            None => print!("{}", content),
+        }
+    } else if do_rclone {
+        let sync_options = SyncOptions {
+            full: options.full,
+            dry_run,
+            adopt: options.adopt_remotes,
+            quiet: options.quiet,
+            assume_yes: options.assume_yes,
+            vaults: vault_scope(&all_vaults, &vaults_to_process, config),
+        };
+        match rclone::sync_remotes(&rclone_entries, config, &sync_options, &mut report.timings) {
+            Ok(summary) => {
+                for warning in summary.iter().flat_map(|s| &s.warnings) {
+                    report.errors.add_warning(format!("rclone: {}", warning));
+                }
+                if let Some(skipped) = summary
+                    .as_ref()
+                    .map(|s| &s.skipped_deletions)
+                    .filter(|names| !names.is_empty())
+                {
+                    report.errors.add_warning(format!(
+                        "--full would delete {} managed rclone remote(s) ({}); skipped. Pass --yes (--force) to delete them.",
+                        skipped.len(),
+                        skipped.join(", ")
+                    ));
+                }
+                report.rclone_sync = summary;
+            }
+            Err(e) => report.errors.add("Rclone sync", e)?,
+        }
+    }
+
+    report.rclone_entries = rclone_entries;
+
+    Ok(report)
+}
+
+/// Resolve the rclone config password the way a real run would and report
+/// whether it worked. The password itself is dropped right away; nothing is
+/// exported or written.
+fn check_rclone_password(
+    backend: &dyn SecretBackend,
+    config: &Config,
+    report: &mut RunReport,
+    log: &dyn Fn(&str),
+) {
+    let source = rclone::get_config_path(config)
+        .and_then(|config_path| rclone::password_source(config, &config_path));
+    match source {
+        Ok(rclone::PasswordSource::Environment(_)) => {
+            log("rclone password: RCLONE_CONFIG_PASS is set (not looked up)");
+        }
+        Ok(rclone::PasswordSource::PassPath(path)) => match backend.get_item_field(&path) {
+            Ok(password) if !password.is_empty() => {
+                log(&format!("rclone password: {} resolved", path));
+            }
+            Ok(_) => report.errors.add_warning(format!(
+                "rclone password path {} resolved to an empty value",
+                path
+            )),
+            Err(e) => report.errors.add_warning(format!(
+                "rclone password path {} did not resolve: {:#}",
+                path, e
+            )),
+        },
+        Err(e) => report
+            .errors
+            .add_warning(format!("rclone password could not be checked: {:#}", e)),
+    }
+    log("");
+}
+
+/// The JSON export when given, otherwise `kind` once pass-cli is checked
+fn open_backend(
+    config: &Config,
+    kind: BackendKind,
+    from_json: Option<&Path>,
+) -> Result<Box<dyn SecretBackend>> {
+    if let Some(path) = from_json {
+        return backend::from_json(path, config.field_names.clone());
+    }
+    let backend = backend::create(kind, config.field_names.clone())?;
+    check_pass_cli()?;
+    Ok(backend)
+}
+
+/// Names of the vaults matching `vault_patterns` (empty uses `default_vaults`
+/// from config), without `skip_vaults`. Nothing is extracted.
+pub fn list_vaults(
+    config: &Config,
+    backend: BackendKind,
+    from_json: Option<&Path>,
+    vault_patterns: &[String],
+) -> Result<Vec<String>> {
+    let backend = open_backend(config, backend, from_json)?;
+    matching_vaults(backend.as_ref(), config, vault_patterns)
+}
+
+/// (vault, title) of the processable items in the matching vaults whose titles
+/// match `item_patterns` (empty uses `default_items` from config) and that carry
+/// `labels` (combined per `label_match`). Nothing is extracted.
+pub fn list_items(
+    config: &Config,
+    backend: BackendKind,
+    from_json: Option<&Path>,
+    vault_patterns: &[String],
+    item_patterns: &[String],
+    labels: &[String],
+) -> Result<Vec<(String, String)>> {
+    let backend = open_backend(config, backend, from_json)?;
+    let vaults = matching_vaults(backend.as_ref(), config, vault_patterns)?;
+
+    let patterns = if item_patterns.is_empty() {
+        &config.default_items
+    } else {
+        item_patterns
+    };
+    let mut items = Vec::new();
+    for vault in vaults {
+        let mut titles: Vec<String> = backend
+            .list_all_items(&vault, config.include_attachments)
+            .with_context(|| format!("Failed to list items in vault '{}'", vault))?
+            .into_iter()
+            .filter(|item| matches_any_pattern(&item.title, patterns, config.case_insensitive))
+            // Items without label data are kept, as in a run
+            .filter(|item| {
+                labels.is_empty()
+                    || item.labels.as_deref().is_none_or(|item_labels| {
+                        matches_labels(item_labels, labels, config.label_match)
+                    })
+            })
+            .map(|item| item.title)
+            .collect();
+        titles.sort();
+        items.extend(titles.into_iter().map(|title| (vault.clone(), title)));
+    }
+    for warning in backend.take_warnings() {
+        eprintln!("Warning: {}", warning);
+    }
+    Ok(items)
+}
+
+fn matching_vaults(
+    backend: &dyn SecretBackend,
+    config: &Config,
+    vault_patterns: &[String],
+) -> Result<Vec<String>> {
+    let patterns = if vault_patterns.is_empty() {
+        &config.default_vaults
+    } else {
+        vault_patterns
+    };
+    Ok(filter_vaults(
+        &backend.list_all_vaults()?,
+        patterns,
+        &config.skip_vaults,
+        config.case_insensitive,
+    ))
+}
+
+/// Check that ssh-keygen is installed and, with `pass_cli`, that pass-cli is
+/// installed and Proton Pass is logged in
+pub fn check_dependencies(pass_cli: bool) -> Result<()> {
+    if pass_cli {
+        check_pass_cli()?;
+    }
+
+    if which::which("ssh-keygen").is_err() {
+        return Err(
+            ExitCode::MissingDependency.error("ssh-keygen not found. Install OpenSSH first.")
+        );
+    }
+
+    Ok(())
+}
+
+/// Check that pass-cli is installed and logged in (launching a login if not)
+fn check_pass_cli() -> Result<()> {
+    if which::which("pass-cli").is_err() {
+        return Err(
+            ExitCode::MissingDependency.error("pass-cli not found. Install Proton Pass CLI first.")
+        );
+    }
+
+    // Check if logged in (with spinner since this can be slow)
+    let spinner = progress::spinner("Checking Proton Pass login...");
+    let output = std::process::Command::new("pass-cli")
+        .arg("info")
+        .output()?;
+    spinner.finish_and_clear();
+
+    if !output.status.success() {
+        if !interactive::can_login() {
+            return Err(ExitCode::Auth.error("Not logged into Proton Pass. Run 'pass-cli login' first (automatic login is off: --no-login, auto_login = false, or no terminal)."));
+        }
+        eprintln!("Not logged into Proton Pass. Launching login...");
+        eprintln!();
+
+        // Try to login interactively
+        proton_pass::login()?;
+
+        eprintln!();
+    }
+
+    tools::PASS_CLI.warn_if_outdated();
+
+    Ok(())
+}
+
+fn handle_purge(
+    config: &Config,
+    dry_run: bool,
+    quiet: bool,
+    do_ssh: bool,
+    do_rclone: bool,
+) -> Result<()> {
+    if !quiet {
+        println!("Purging managed resources...");
+    }
+
+    // Delete SSH keys folder
+    if do_ssh {
+        let ssh_dir = config.expanded_ssh_output_dir()?;
+        if ssh_dir.exists() {
+            if dry_run {
+                if !quiet {
+                    println!("  Would remove {}", ssh_dir.display());
+                }
+            } else {
+                std::fs::remove_dir_all(&ssh_dir)?;
+                if !quiet {
+                    println!("  Removed {}", ssh_dir.display());
+                }
+            }
+        } else if !quiet {
+            println!("  {} does not exist", ssh_dir.display());
+        }
+    }
+
+    // Delete managed rclone remotes
+    if do_rclone {
+        rclone::purge_managed_remotes(config, None, None, dry_run, quiet)?;
+    }
+
+    if !quiet {
+        println!("Done.");
+    }
+    Ok(())
+}
+
+/// Purge only the output of items matching --vault/--item/--label: their key files,
+/// managed SSH config stanzas and managed rclone remotes
+fn handle_filtered_purge(
+    backend: &dyn SecretBackend,
+    config: &Config,
+    options: &ExportOptions,
+    do_ssh: bool,
+    do_rclone: bool,
+) -> Result<()> {
+    let dry_run = options.dry_run;
+    let log = |msg: &str| {
+        if !options.quiet {
+            println!("{}", msg);
+        }
+    };
+    log("Purging managed resources of matching items...");
+
+    // Resolve what the matching items would have produced
+    let all_vaults = backend.list_all_vaults()?;
+    let vaults = filter_vaults(
+        &all_vaults,
+        &options.vaults,
+        &config.skip_vaults,
+        config.case_insensitive,
+    );
+    let ssh_dir = config.expanded_ssh_output_dir()?;
+
+    let mut key_files = Vec::new();
+    let mut hosts = HashSet::new();
+    let mut remotes = HashSet::new();
+    for vault in &vaults {
+        let items = backend
+            .list_all_items(vault, config.include_attachments)
+            .with_context(|| format!("Failed to list items in vault '{}'", vault))?;
+        // Items without label data can't be shown to match, so they are kept
+        for item in items
+            .iter()
+            .filter(|item| {
+                matches_any_pattern(&item.title, &options.items, config.case_insensitive)
+            })
+            .filter(|item| {
+                options.labels.is_empty()
+                    || item.labels.as_deref().is_some_and(|item_labels| {
+                        matches_labels(item_labels, &options.labels, config.label_match)
+                    })
+            })
+        {
+            if item.private_key.is_some() {
+                key_files.push(ssh_dir.join(ssh::key_file_name(config.key_naming, vault, item)));
+            }
+            let (host_names, remote_names) = ssh::item_names(item);
+            hosts.extend(host_names);
+            remotes.extend(remote_names.iter().map(|n| config.rclone_remote_name(n)));
+        }
+    }
+    for warning in backend.take_warnings() {
+        eprintln!("Warning: {}", warning);
+    }
+
+    if do_ssh {
+        let mut removed_keys = 0;
+        for privkey_path in &key_files {
+            for path in [
+                privkey_path.clone(),
+                ssh::public_key_path(privkey_path),
+                ssh::certificate_path(privkey_path),
+            ] {
+                if !path.exists() {
+                    continue;
+                }
+                if dry_run {
+                    log(&format!("  Would remove {}", path.display()));
+                } else {
+                    std::fs::remove_file(&path)?;
+                    log(&format!("  Removed {}", path.display()));
+                }
+                removed_keys += 1;
+            }
+            // Drop a per-vault directory left empty (fails harmlessly otherwise)
+            if let Some(parent) = privkey_path.parent().filter(|p| *p != ssh_dir) {
+                if !dry_run {
+                    std::fs::remove_dir(parent).ok();
+                }
+            }
+        }
+
+        let mut removed_hosts = Vec::new();
+        if ssh_dir.join("config").exists() {
+            let mut ssh_manager =
+                SshManager::new(&ssh_dir, false, dry_run, options.assume_yes, config)?;
+            removed_hosts = ssh_manager.remove_hosts(&hosts);
+            if !removed_hosts.is_empty() {
+                ssh_manager.write_config()?;
+            }
+        }
+        for host in &removed_hosts {
+            if dry_run {
+                log(&format!("  Would remove SSH config stanza: {}", host));
+            } else {
+                log(&format!("  Removed SSH config stanza: {}", host));
+            }
+        }
+
+        if removed_keys == 0 && removed_hosts.is_empty() {
+            log("  No matching SSH keys or config stanzas found");
+        }
+    }
+
+    if do_rclone {
+        rclone::purge_managed_remotes(
+            config,
+            Some(&remotes),
+            vault_scope(&all_vaults, &vaults, config),
+            dry_run,
+            options.quiet,
+        )?;
+    }
+
+    log("Done.");
+    Ok(())
+}
+
+/// Back the tool out of this machine: remove the SSH output directory, the
+/// managed rclone remotes, the `Include` of the generated config in
+/// `~/.ssh/config` and (optionally) the config file, then list what went
+pub fn run_uninstall(config: &Config, options: &UninstallOptions) -> Result<()> {
+    let dry_run = options.dry_run;
+    let log = |msg: &str| {
+        if !options.quiet {
+            println!("{}", msg);
+        }
+    };
+
+    let ssh_dir = config.expanded_ssh_output_dir()?;
+    let user_config = ssh::user_config_path()?;
+    let include_target = ssh_dir.join("config");
+
+    let mut plan = vec![format!(
+        "Managed SSH keys and config ({})",
+        ssh_dir.display()
+    )];
+    if config.rclone.enabled {
+        plan.push("Managed rclone remotes".to_string());
+    }
+    plan.push(format!(
+        "The Include of {} in {}",
+        include_target.display(),
+        user_config.display()
+    ));
+    if let Some(ref path) = options.remove_config {
+        plan.push(format!("The config file {}", path.display()));
+    }
+
+    if dry_run {
+        log("[DRY RUN] No changes will be made");
+        log("");
+    } else {
+        confirm_uninstall(&plan, options)?;
+    }
+
+    let _lock = if dry_run {
+        None
+    } else {
+        Some(InstanceLock::acquire()?)
+    };
+
+    let verb = if dry_run { "Would remove" } else { "Removed" };
+    let mut removed = Vec::new();
+
+    if ssh_dir.exists() {
+        if !dry_run {
+            std::fs::remove_dir_all(&ssh_dir)
+                .with_context(|| format!("Failed to remove {}", ssh_dir.display()))?;
+        }
+        log(&format!("  {} {}", verb, ssh_dir.display()));
+        removed.push(format!("SSH output directory {}", ssh_dir.display()));
+    } else {
+        log(&format!("  {} does not exist", ssh_dir.display()));
+    }
+
+    if config.rclone.enabled {
+        let remotes = rclone::purge_managed_remotes(config, None, None, dry_run, options.quiet)?;
+        if remotes > 0 {
+            removed.push(format!("{} managed rclone remote(s)", remotes));
+        }
+    }
+
+    let includes = ssh::remove_include(&user_config, &include_target, dry_run)?;
+    if includes > 0 {
+        log(&format!(
+            "  {} the Include line from {}",
+            verb,
+            user_config.display()
+        ));
+        removed.push(format!("Include line in {}", user_config.display()));
+    } else {
+        log(&format!("  No Include line in {}", user_config.display()));
+    }
+
+    if let Some(ref path) = options.remove_config {
+        if path.exists() {
+            if !dry_run {
+                std::fs::remove_file(path)
+                    .with_context(|| format!("Failed to remove {}", path.display()))?;
+            }
+            log(&format!("  {} {}", verb, path.display()));
+            removed.push(format!("Config file {}", path.display()));
+        }
+    }
+
+    log("");
+    if removed.is_empty() {
+        log("Nothing to remove.");
+    } else {
+        log(&format!(
+            "{}:",
+            if dry_run {
+                "Uninstall would remove"
+            } else {
+                "Uninstalled"
+            }
+        ));
+        for entry in &removed {
+            log(&format!("  - {}", entry));
+        }
+    }
+    if options.remove_config.is_none() && !options.quiet {
+        println!("The config file was kept (pass --remove-config to delete it too).");
+    }
+    Ok(())
+}
+
+/// Ask before uninstalling. Fails instead of asking when there is no terminal
+/// to ask on (or output is quiet); `--yes` proceeds.
+fn confirm_uninstall(plan: &[String], options: &UninstallOptions) -> Result<()> {
+    if !options.quiet {
+        println!("This will DELETE from this machine:");
+        for entry in plan {
+            println!("  - {}", entry);
+        }
+        println!("Proton Pass will NOT be modified.");
+        println!();
+    }
+
+    if options.assume_yes {
+        if !options.quiet {
+            println!("Skipping uninstall confirmation (--yes).");
+        }
+        return Ok(());
+    }
+    if options.quiet || !interactive::is_interactive() {
+        return Err(ExitCode::Usage
+            .error("--uninstall needs confirmation; pass --yes to run it without a terminal"));
+    }
+
+    let proceed = inquire::Confirm::new("Uninstall?")
+        .with_default(false)
+        .prompt()
+        .unwrap_or(false);
+    if !proceed {
+        anyhow::bail!("Aborted: nothing was removed");
+    }
+    Ok(())
+}
+
+/// Items of one vault that passed the filters
+struct VaultSelection {
+    /// Number of items fetched before filtering
+    fetched: usize,
+    /// Items to process
+    items: Vec<SshItem>,
+    /// Log lines for items skipped on purpose (e.g. other machines)
+    skipped: Vec<String>,
+    /// Items kept despite label filters because their labels are unknown
+    unlabeled: usize,
+}
+
+/// Apply the item patterns, labels, Teleport-only and machine-suffix filters
+fn select_items(
+    items: Vec<SshItem>,
+    item_patterns: &[String],
+    labels: &[String],
+    label_match: LabelMatch,
+    case_insensitive: bool,
+    do_rclone: bool,
+    machine_names: &[String],
+) -> VaultSelection {
+    let fetched = items.len();
+    let mut selected = Vec::new();
+    let mut skipped = Vec::new();
+    let mut unlabeled = 0;
+
+    for item in items {
+        // Filter by item patterns
+        if !matches_any_pattern(&item.title, item_patterns, case_insensitive) {
+            continue;
+        }
+
+        // Filter by labels; without label data the filter can't be applied
+        if !labels.is_empty() {
+            match &item.labels {
+                Some(item_labels) => {
+                    if !matches_labels(item_labels, labels, label_match) {
+                        continue;
+                    }
+                }
+                None => unlabeled += 1,
+            }
+        }
+
+        if item.disabled {
+            skipped.push(format!("  Skipping: {} (disabled)", item.title));
+            continue;
+        }
+
+        // Skip Teleport-only items (no host, has ssh command) when not doing rclone
+        let is_teleport_only = item.host.is_none() && item.ssh.is_some();
+        if is_teleport_only && !do_rclone {
+            continue;
+        }
+
+        // Check machine-specific suffix (comma-separated, any name may match)
+        if let Some((_, suffix)) = item.title.rsplit_once('/') {
+            let for_this_machine = suffix
+                .split(',')
+                .map(|name| name.trim().to_lowercase())
+                .any(|name| machine_names.contains(&name));
+            if !for_this_machine {
+                skipped.push(format!("  Skipping: {} (not for this machine)", item.title));
+                continue;
+            }
+        }
+
+        selected.push(item);
+    }
+
+    VaultSelection {
+        fetched,
+        items: selected,
+        skipped,
+        unlabeled,
+    }
+}
+
+/// The vaults a run was limited to, or None when it covers every vault that
+/// `skip_vaults` leaves (so untagged rclone remotes are still in scope)
+fn vault_scope<'a>(
+    all_vaults: &[String],
+    processed: &'a [String],
+    config: &Config,
+) -> Option<&'a [String]> {
+    let unlimited = filter_vaults(
+        all_vaults,
+        &[],
+        &config.skip_vaults,
+        config.case_insensitive,
+    );
+    (processed.len() < unlimited.len()).then_some(processed)
+}
+
+/// Check an item's labels against the `--label` filters (case-insensitive)
+fn matches_labels(item_labels: &[String], wanted: &[String], label_match: LabelMatch) -> bool {
+    let has = |label: &String| {
+        item_labels
+            .iter()
+            .any(|l| l.trim().eq_ignore_ascii_case(label.trim()))
+    };
+    match label_match {
+        LabelMatch::Any => wanted.iter().any(has),
+        LabelMatch::All => wanted.iter().all(has),
+    }
+}
+
+/// Lowercased names that select this machine in a `title/<machine>` suffix:
+/// the hostname, its first DNS label and the configured `machine_tags`
+fn machine_names(config: &Config) -> Vec<String> {
+    let hostname = platform::get_hostname().to_lowercase();
+    let mut names = vec![hostname.clone()];
+    if let Some((short, _)) = hostname.split_once('.') {
+        names.push(short.to_string());
+    }
+    names.extend(
+        config
+            .machine_tags
+            .iter()
+            .map(|tag| tag.trim().to_lowercase())
+            .filter(|tag| !tag.is_empty()),
+    );
+    names
+}
+
+/// Ask before processing more items than `max_items`. Fails instead of asking
+/// when there is no terminal to ask on (or output is quiet); `--yes` proceeds.
+fn confirm_item_count(count: usize, max_items: usize, options: &ExportOptions) -> Result<()> {
+    let message = format!(
+        "{} items matched, more than max_items ({})",
+        count, max_items
+    );
+
+    if options.assume_yes {
+        if !options.quiet {
+            println!("{} - continuing (--yes)", message);
+        }
+        return Ok(());
+    }
+    if options.quiet || !interactive::is_interactive() {
+        return Err(ExitCode::Usage.error(format!(
+            "{}. Narrow --vault/--item, raise --max-items, or pass --yes",
+            message
+        )));
+    }
+
+    let proceed = inquire::Confirm::new(&format!("{}. Continue?", message))
+        .with_default(false)
+        .prompt()
+        .unwrap_or(false);
+    if !proceed {
+        anyhow::bail!("Aborted: {}", message);
+    }
+    Ok(())
+}
+
+/// Filter names by glob patterns (no patterns keeps everything)
+pub fn filter_by_patterns(
+    items: &[String],
+    patterns: &[String],
+    case_insensitive: bool,
+) -> Vec<String> {
+    if patterns.is_empty() {
+        return items.to_vec();
+    }
+
+    items
+        .iter()
+        .filter(|item| matches_any_pattern(item, patterns, case_insensitive))
+        .cloned()
+        .collect()
+}
+
+/// Filter vaults by glob patterns, dropping reserved vaults (`skip_vaults`).
+/// A skipped vault is only processed when a pattern names it exactly, so a
+/// wildcard like `*` never pulls in Trash.
+pub fn filter_vaults(
+    vaults: &[String],
+    patterns: &[String],
+    skip: &[String],
+    case_insensitive: bool,
+) -> Vec<String> {
+    filter_by_patterns(vaults, patterns, case_insensitive)
+        .into_iter()
+        .filter(|vault| {
+            !proton_pass::is_skipped_vault(vault, skip)
+                || patterns
+                    .iter()
+                    .any(|p| is_literal_pattern(p) && matches_pattern(vault, p, case_insensitive))
+        })
+        .collect()
+}
+
+/// Check whether a pattern is a plain name (no glob metacharacters)
+pub fn is_literal_pattern(pattern: &str) -> bool {
+    !pattern.contains(['*', '?', '['])
+}
+
+/// Check whether a name matches a pattern. An exact match always counts, so
+/// titles containing `[` or `*` can be named literally; a pattern that is not
+/// a valid glob (e.g. an unclosed `[`) matches only exactly. With
+/// `case_insensitive` (the config option), case is ignored.
+pub fn matches_pattern(item: &str, pattern: &str, case_insensitive: bool) -> bool {
+    let (item, pattern) = if case_insensitive {
+        (item.to_lowercase(), pattern.to_lowercase())
+    } else {
+        (item.to_string(), pattern.to_string())
+    };
+
+    if item == pattern {
+        return true;
+    }
+    if is_literal_pattern(&pattern) {
+        return false;
+    }
+    glob::Pattern::new(&pattern).is_ok_and(|glob_pattern| glob_pattern.matches(&item))
+}
+
+/// Check whether a name matches any pattern (no patterns matches everything)
+pub fn matches_any_pattern(item: &str, patterns: &[String], case_insensitive: bool) -> bool {
+    patterns.is_empty()
+        || patterns
+            .iter()
+            .any(|pattern| matches_pattern(item, pattern, case_insensitive))
+}
+
+/// Import Teleport nodes as rclone-compatible custom items in a Proton Pass vault
+pub fn import_from_tsh(options: &TshImportOptions) -> Result<TshImportReport> {
+    let dry_run = options.dry_run;
+    let quiet = options.quiet;
+
+    // Helper for logging
+    let log = |msg: &str| {
+        if !quiet {
+            println!("{}", msg);
+        }
+    };
+
+    if dry_run {
+        log("[DRY RUN] No changes will be made");
+        log("");
+    }
+
+    let (teleport, proxy, filtered_nodes) = connect_tsh(options, &log)?;
+    if filtered_nodes.is_empty() {
+        return Ok(TshImportReport::default());
+    }
+
+    // Teleport items are created by this tool, so their field names are the defaults
+    let backend = backend::create(options.backend, FieldNames::default())?;
+
+    let mut report = TshImportReport::default();
+    let groups = group_nodes_by_vault(options, filtered_nodes, &mut report.warnings);
+    for warning in &report.warnings {
+        log(&format!("  {}", warning));
+    }
+    for (vault_name, hostnames) in &groups {
+        if options.vault_label.is_some() {
+            log(&format!("[{}]", vault_name));
+        }
+        let counts = import_into_vault(
+            backend.as_ref(),
+            &teleport,
+            &proxy,
+            options,
+            vault_name,
+            hostnames,
+            &log,
+        )?;
+        report.created += counts.created;
+        report.updated += counts.updated;
+        report.skipped += counts.skipped;
+        report.warnings.extend(counts.warnings);
+    }
+
+    Ok(report)
+}
+
+/// Group node hostnames by target vault: the single `--vault`, or the node's value
+/// for `--vault-by-label` (falling back to `--vault`, when given, if the label is missing).
+/// Nodes with nowhere to go are reported in `warnings`.
+fn group_nodes_by_vault(
+    options: &TshImportOptions,
+    nodes: Vec<Node>,
+    warnings: &mut Vec<String>,
+) -> BTreeMap<String, Vec<String>> {
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for node in nodes {
+        let Some(ref key) = options.vault_label else {
+            groups
+                .entry(options.vault.clone())
+                .or_default()
+                .push(node.hostname);
+            continue;
+        };
+
+        let labelled = node
+            .labels
+            .get(key)
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty());
+        match labelled {
+            Some(vault) => groups
+                .entry(vault.to_string())
+                .or_default()
+                .push(node.hostname),
+            None if !options.vault.is_empty() => groups
+                .entry(options.vault.clone())
+                .or_default()
+                .push(node.hostname),
+            None => warnings.push(format!(
+                "{}: skipped (no '{}' label and no fallback --vault)",
+                node.hostname, key
+            )),
+        }
+    }
+    groups
+}
+
+/// Create (or update) items for Teleport nodes in one vault, creating the vault if needed
+fn import_into_vault(
+    backend: &dyn SecretBackend,
+    teleport: &Teleport,
+    proxy: &str,
+    options: &TshImportOptions,
+    vault_name: &str,
+    hostnames: &[String],
+    log: &impl Fn(&str),
+) -> Result<TshImportReport> {
+    let dry_run = options.dry_run;
+    let quiet = options.quiet;
+
+    if !backend.vault_exists(vault_name)? {
+        if dry_run {
+            log(&format!("[DRY RUN] Would create vault: {}", vault_name));
+        } else {
+            let spinner = if !quiet {
+                Some(progress::spinner(&format!(
+                    "Creating vault '{}'...",
+                    vault_name
+                )))
+            } else {
+                None
+            };
+
+            backend.create_vault(vault_name)?;
+
+            if let Some(sp) = spinner {
+                sp.finish_and_clear();
+            }
+            log(&format!("Created vault: {}", vault_name));
+        }
+    }
+
+    // 9. Get existing items in vault (any type, not just SSH keys)
+    let existing_titles: HashSet<String> = backend
+        .list_item_titles(vault_name)
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+
+    // Current Teleport fields of existing items (only needed for --update-existing)
+    let existing_tsh_items: HashMap<String, SshItem> = if options.update_existing {
+        backend
+            .list_teleport_items(vault_name)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|item| (item.title.clone(), item))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+
+    // Scan the nodes that get an item written up front, several at once
+    let to_scan: Vec<&str> = hostnames
+        .iter()
+        .filter(|hostname| {
+            !existing_titles.contains(*hostname)
+                || (options.update_existing && existing_tsh_items.contains_key(*hostname))
+        })
+        .map(String::as_str)
+        .collect();
+    // Starts with what went wrong listing the existing items
+    let mut warnings = backend.take_warnings();
+    let server_commands = tsh_server_commands(teleport, options, &to_scan, &mut warnings);
+
+    // 10. Process nodes with progress bar
+    let pb = if !quiet {
+        Some(progress::node_progress_bar(hostnames.len() as u64))
+    } else {
+        None
+    };
+
+    let mut created = 0;
+    let mut updated = 0;
+    let mut skipped = 0;
+
+    for (i, hostname) in hostnames.iter().enumerate() {
+        let exists = existing_titles.contains(hostname);
+        let existing_item = existing_tsh_items.get(hostname);
+
+        if exists && !options.update_existing {
+            if let Some(ref pb) = pb {
+                pb.println(format!("  {}: skipped (already exists)", hostname));
+            }
+            skipped += 1;
+        } else if exists && existing_item.is_none() {
+            // Title is taken by an item that isn't a Teleport item - leave it alone
+            if let Some(ref pb) = pb {
+                pb.println(format!("  {}: skipped (not a Teleport item)", hostname));
+            }
+            skipped += 1;
+        } else {
+            let server_command = server_commands[hostname.as_str()].clone();
+
+            // Build SSH command
+            let ssh_command = teleport.ssh_command(proxy, hostname);
+
+            if let Some(item) = existing_item {
+                let ssh_changed = item.ssh.as_deref() != Some(ssh_command.as_str());
+                let server_changed =
+                    item.server_command.as_deref() != Some(server_command.as_str());
+
+                if !ssh_changed && !server_changed {
+                    if let Some(ref pb) = pb {
+                        pb.println(format!("  {}: skipped (unchanged)", hostname));
+                    }
+                    skipped += 1;
+                } else if dry_run {
+                    if let Some(ref pb) = pb {
+                        pb.println(format!("  {}: [DRY RUN] would update", hostname));
+                        if ssh_changed {
+                            pb.println(format!("    SSH: {}", ssh_command));
+                        }
+                        if server_changed {
+                            pb.println(format!("    Server Command: {}", server_command));
+                        }
+                    }
+                    updated += 1;
+                } else {
+                    if let Some(ref pb) = pb {
+                        pb.set_message(format!("Updating {}...", hostname));
+                    }
+
+                    if ssh_changed {
+                        backend.update_item_field(vault_name, hostname, "SSH", &ssh_command)?;
+                    }
+                    if server_changed {
+                        backend.update_item_field(
+                            vault_name,
+                            hostname,
+                            "Server Command",
+                            &server_command,
+                        )?;
+                    }
+
+                    if let Some(ref pb) = pb {
+                        pb.set_message("");
+                        pb.println(format!("  {}: updated", hostname));
+                    }
+                    updated += 1;
+                }
+            } else if dry_run {
+                if let Some(ref pb) = pb {
+                    pb.println(format!("  {}: [DRY RUN] would create", hostname));
+                    pb.println(format!("    SSH: {}", ssh_command));
+                    pb.println(format!("    Server Command: {}", server_command));
+                }
+                created += 1;
+            } else {
+                // Create item (with spinner message on progress bar)
+                if let Some(ref pb) = pb {
+                    pb.set_message(format!("Creating {}...", hostname));
+                }
+
+                backend.create_tsh_item(vault_name, hostname, &ssh_command, &server_command)?;
+
+                if let Some(ref pb) = pb {
+                    pb.set_message("");
+                    pb.println(format!("  {}: created", hostname));
+                }
+                created += 1;
+            }
+        }
+
+        if let Some(ref pb) = pb {
+            pb.set_position(i as u64 + 1);
+        }
+    }
+
+    if let Some(pb) = pb {
+        pb.finish_and_clear();
+    }
+
+    log("");
+    if options.update_existing {
+        if dry_run {
+            log(&format!(
+                "[DRY RUN] Would add {} and update {} Teleport node(s) in vault \"{}\" ({} unchanged or skipped)",
+                created, updated, vault_name, skipped
+            ));
+        } else {
+            log(&format!(
+                "Done! Added {} and updated {} Teleport node(s) in vault \"{}\" ({} skipped)",
+                created, updated, vault_name, skipped
+            ));
+        }
+    } else if dry_run {
+        log(&format!(
+            "[DRY RUN] Would add {} Teleport node(s) to vault \"{}\" ({} already exist)",
+            created, vault_name, skipped
+        ));
+    } else {
+        log(&format!(
+            "Done! Added {} Teleport node(s) to vault \"{}\" ({} skipped)",
+            created, vault_name, skipped
+        ));
+    }
+
+    Ok(TshImportReport {
+        created,
+        updated,
+        skipped,
+        warnings,
+    })
+}
+
+/// Check tsh, log in status and proxy, then list the nodes matching `--item`
+fn connect_tsh(
+    options: &TshImportOptions,
+    log: &impl Fn(&str),
+) -> Result<(Teleport, String, Vec<Node>)> {
+    let quiet = options.quiet;
+
+    // 3. Check tsh is installed
+    if which::which("tsh").is_err() {
+        return Err(ExitCode::MissingDependency.error("tsh not found. Install Teleport CLI first."));
+    }
+    tools::TSH.warn_if_outdated();
+
+    // 4. Check tsh login status
+    let spinner = if !quiet {
+        Some(progress::spinner("Checking Teleport login..."))
+    } else {
+        None
+    };
+
+    let teleport = Teleport::with_cluster(options.cluster.clone());
+    let status = match teleport.get_status() {
+        Ok(s) => {
+            if let Some(sp) = spinner {
+                sp.finish_and_clear();
+            }
+            s
+        }
+        Err(e) => {
+            if let Some(sp) = spinner {
+                sp.finish_and_clear();
+            }
+            return Err(e);
+        }
+    };
+
+    log(&format!(
+        "Logged in to {} as {}",
+        status.cluster, status.username
+    ));
+    if let Some(ref cluster) = options.cluster {
+        log(&format!("Using cluster: {}", cluster));
+    }
+    log("");
+
+    // 5. Get proxy address
+    let proxy = teleport.resolve_proxy(&status, options.proxy.as_deref())?;
+    if options.proxy.is_some() {
+        log(&format!("Using proxy: {}", proxy));
+    }
+
+    // 6. List nodes
+    let spinner = if !quiet {
+        Some(progress::spinner("Fetching Teleport nodes..."))
+    } else {
+        None
+    };
+
+    let nodes = teleport.list_nodes()?;
+
+    if let Some(sp) = spinner {
+        sp.finish_and_clear();
+    }
+
+    // 7. Filter nodes by --item patterns (if provided)
+    let item_patterns = &options.items;
+    let filtered_nodes: Vec<Node> = nodes
+        .into_iter()
+        .filter(|n| matches_any_pattern(&n.hostname, item_patterns, options.case_insensitive))
+        .collect();
+
+    if filtered_nodes.is_empty() {
+        log("No nodes matched the specified patterns.");
+    } else {
+        log(&format!(
+            "Found {} node(s) to process",
+            filtered_nodes.len()
+        ));
+        log("");
+    }
+
+    Ok((teleport, proxy, filtered_nodes))
+}
+
+/// sftp-server path for each node: `--assume-os`, the default with `--no-scan`,
+/// otherwise detected over `tsh ssh` on up to `--parallel-tsh-scan` nodes at
+/// once. A node whose scan fails gets the default path and a warning.
+fn tsh_server_commands<'a>(
+    teleport: &Teleport,
+    options: &TshImportOptions,
+    hostnames: &[&'a str],
+    warnings: &mut Vec<String>,
+) -> HashMap<&'a str, String> {
+    if let Some(os) = options.assume_os {
+        let path = os.sftp_server_path();
+        return hostnames.iter().map(|h| (*h, path.to_string())).collect();
+    }
+    if options.no_scan {
+        return hostnames
+            .iter()
+            .map(|h| (*h, DEFAULT_SFTP_SERVER.to_string()))
+            .collect();
+    }
+
+    let pb = (!options.quiet).then(|| progress::scan_progress_bar(hostnames.len() as u64));
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(HashMap::new());
+    let failures = Mutex::new(Vec::new());
+
+    std::thread::scope(|scope| {
+        for _ in 0..options.parallel_scan.clamp(1, hostnames.len().max(1)) {
+            scope.spawn(|| {
+                while let Some(hostname) = hostnames.get(next.fetch_add(1, Ordering::SeqCst)) {
+                    let path = teleport.get_subsystem(hostname).unwrap_or_else(|e| {
+                        failures
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .push(format!(
+                                "{}: sftp-server scan failed ({:#}); using {}",
+                                hostname, e, DEFAULT_SFTP_SERVER
+                            ));
+                        DEFAULT_SFTP_SERVER.to_string()
+                    });
+                    results
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .insert(*hostname, path);
+                    if let Some(ref pb) = pb {
+                        pb.inc(1);
+                    }
+                }
+            });
+        }
+    });
+
+    if let Some(pb) = pb {
+        pb.finish_and_clear();
+    }
+    let mut failures = failures.into_inner().unwrap_or_else(|e| e.into_inner());
+    failures.sort();
+    warnings.extend(failures);
+    results.into_inner().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Create rclone remotes for Teleport nodes directly, without staging items
+/// in Proton Pass. Returns the entries that were synced and the scan warnings.
+pub fn rclone_from_tsh(
+    config: &Config,
+    options: &TshImportOptions,
+) -> Result<(Vec<RcloneEntry>, Vec<String>)> {
+    let quiet = options.quiet;
+    let log = |msg: &str| {
+        if !quiet {
+            println!("{}", msg);
+        }
+    };
+
+    if options.dry_run {
+        log("[DRY RUN] No changes will be made");
+        log("");
+    }
+
+    // The rclone config is modified, so keep other instances out
+    let _lock = if options.dry_run {
+        None
+    } else {
+        Some(InstanceLock::acquire()?)
+    };
+
+    let (teleport, proxy, nodes) = connect_tsh(options, &log)?;
+    if nodes.is_empty() {
+        return Ok((Vec::new(), Vec::new()));
+    }
+
+    let hostnames: Vec<&str> = nodes.iter().map(|node| node.hostname.as_str()).collect();
+    let mut warnings = Vec::new();
+    let mut server_commands = tsh_server_commands(&teleport, options, &hostnames, &mut warnings);
+
+    let pb = if !quiet {
+        Some(progress::node_progress_bar(nodes.len() as u64))
+    } else {
+        None
+    };
+
+    let mut entries = Vec::new();
+    for (i, node) in nodes.iter().enumerate() {
+        let hostname = &node.hostname;
+        let server_command = server_commands
+            .remove(hostname.as_str())
+            .unwrap_or_else(|| DEFAULT_SFTP_SERVER.to_string());
+        entries.push(RcloneEntry {
+            remote_name: sanitize_name(hostname),
+            host: None,
+            user: String::new(),
+            port: None,
+            key_file: String::new(),
+            key_use_agent: false,
+            other_aliases: String::new(),
+            ssh: Some(teleport.ssh_command(&proxy, hostname)),
+            jump: None,
+            server_command: Some(server_command),
+            shell_type: None,
+            md5sum_command: None,
+            sha1sum_command: None,
+            vault: None,
+        });
+        if let Some(ref pb) = pb {
+            pb.set_position(i as u64 + 1);
+        }
+    }
+
+    if let Some(pb) = pb {
+        pb.finish_and_clear();
+    }
+
+    rclone::sync_remotes(
+        &entries,
+        config,
+        &SyncOptions {
+            dry_run: options.dry_run,
+            quiet,
+            ..SyncOptions::default()
+        },
+        &mut Timings::new(),
+    )?;
+
+    Ok((entries, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn bracketed_titles_match_literally() {
+        assert!(matches_pattern("web [prod]", "web [prod]", false));
+        assert!(!matches_pattern("web x", "web [prod]", false));
+        assert!(matches_pattern("web*", "web*", false));
+        assert!(matches_pattern("db [unclosed", "db [unclosed", false));
+        assert!(!matches_pattern("db x", "db [unclosed", false));
+    }
+
+    #[test]
+    fn globs_still_match() {
+        assert!(matches_pattern("web-prod", "web-*", false));
+        assert!(matches_pattern("web-1", "web-?", false));
+        assert!(!matches_pattern("db-prod", "web-*", false));
+        assert!(matches_any_pattern("anything", &[], false));
+    }
+
+    #[test]
+    fn case_insensitive_matching_is_opt_in() {
+        assert!(!matches_pattern("Web-Prod", "web-prod", false));
+        assert!(!matches_pattern("Web-Prod", "web-*", false));
+        assert!(matches_pattern("Web-Prod", "web-prod", true));
+        assert!(matches_pattern("Web-Prod", "WEB-*", true));
+        assert!(matches_pattern("Web [Prod]", "web [prod]", true));
+    }
+
+    #[test]
+    fn filter_vaults_honours_case_insensitive() {
+        let vaults = names(&["Work", "Personal", "Trash"]);
+        let skip = names(&["Trash"]);
+
+        assert!(filter_vaults(&vaults, &names(&["work"]), &skip, false).is_empty());
+        assert_eq!(
+            filter_vaults(&vaults, &names(&["work"]), &skip, true),
+            names(&["Work"])
+        );
+        assert_eq!(
+            filter_vaults(&vaults, &names(&["*"]), &skip, false),
+            names(&["Work", "Personal"])
+        );
+        assert_eq!(
+            filter_vaults(&vaults, &names(&["trash"]), &skip, true),
+            names(&["Trash"])
+        );
+    }
+}
@@ -0,0 +1,44 @@
+use std::time::{Duration, Instant};
+
+/// Accumulated durations of the major phases of a run (shown with `--stats`)
+#[derive(Debug, Clone, Default)]
+pub struct Timings {
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add elapsed time to a phase (phases keep their first-seen order)
+    pub fn add(&mut self, phase: &'static str, elapsed: Duration) {
+        match self.phases.iter_mut().find(|(name, _)| *name == phase) {
+            Some((_, total)) => *total += elapsed,
+            None => self.phases.push((phase, elapsed)),
+        }
+    }
+
+    /// Run a closure and record its duration under a phase
+    pub fn time<T>(&mut self, phase: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.add(phase, start.elapsed());
+        result
+    }
+
+    /// Get the recorded (phase, duration) pairs
+    pub fn phases(&self) -> &[(&'static str, Duration)] {
+        &self.phases
+    }
+
+    /// Print the timing breakdown to stdout
+    pub fn report(&self, total: Duration) {
+        println!();
+        println!("Timing breakdown:");
+        for (phase, elapsed) in &self.phases {
+            println!("  {:<22} {:>8.2}s", phase, elapsed.as_secs_f64());
+        }
+        println!("  {:<22} {:>8.2}s", "Total", total.as_secs_f64());
+    }
+}
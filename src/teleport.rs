@@ -1,10 +1,181 @@
 use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::process::Command;
 use url::Url;
 
+use crate::error::ExitCode;
+
+/// Fallback sftp-server path when detection fails or is skipped
+pub const DEFAULT_SFTP_SERVER: &str = "/usr/lib/openssh/sftp-server";
+
 /// Interface to Teleport CLI (tsh)
-pub struct Teleport;
+pub struct Teleport {
+    /// Leaf cluster to target (None uses the cluster tsh is logged into)
+    cluster: Option<String>,
+}
+
+/// How much of an unexpected `tsh ls` document to quote in errors
+const JSON_EXCERPT_LEN: usize = 200;
+
+/// Parse `tsh ls --format=json` output: either a bare array of nodes or an
+/// object wrapping them in `items`
+fn parse_nodes(output: &[u8]) -> Result<Vec<Node>> {
+    let document: serde_json::Value =
+        serde_json::from_slice(output).context("Failed to parse tsh ls output")?;
+
+    let entries = match &document {
+        serde_json::Value::Array(entries) => entries,
+        serde_json::Value::Object(map) => match map.get("items") {
+            Some(serde_json::Value::Array(entries)) => entries,
+            _ => bail!(
+                "Unexpected tsh ls output (expected a list of nodes): {}",
+                json_excerpt(&document)
+            ),
+        },
+        _ => bail!(
+            "Unexpected tsh ls output (expected a list of nodes): {}",
+            json_excerpt(&document)
+        ),
+    };
+
+    entries.iter().map(parse_node).collect()
+}
+
+/// Turn one `tsh ls` entry into a Node. Entries whose labels don't have the
+/// expected shape still yield their name, without labels.
+fn parse_node(entry: &serde_json::Value) -> Result<Node> {
+    if let Ok(node) = TeleportNode::deserialize(entry) {
+        let hostname = node
+            .spec
+            .hostname
+            .or(node.metadata.name)
+            .or(node.hostname)
+            .or(node.name);
+        if let Some(hostname) = hostname.filter(|h| !h.is_empty()) {
+            // Command labels are evaluated on the node; static labels win on a clash
+            let mut labels: HashMap<String, String> = node
+                .spec
+                .cmd_labels
+                .into_iter()
+                .map(|(key, label)| (key, label.result.trim().to_string()))
+                .collect();
+            labels.extend(node.metadata.labels);
+            return Ok(Node { hostname, labels });
+        }
+    }
+
+    match find_name(entry) {
+        Some(hostname) => Ok(Node {
+            hostname,
+            labels: HashMap::new(),
+        }),
+        None => bail!(
+            "Unexpected tsh ls output (node without a hostname or name): {}",
+            json_excerpt(entry)
+        ),
+    }
+}
+
+/// First non-empty string `hostname` (preferred) or `name` field anywhere in
+/// a JSON value, searching shallower levels first
+fn find_name(value: &serde_json::Value) -> Option<String> {
+    let mut level = vec![value];
+    while !level.is_empty() {
+        for key in ["hostname", "name"] {
+            let found = level.iter().find_map(|v| match v.get(key) {
+                Some(serde_json::Value::String(s)) if !s.is_empty() => Some(s.clone()),
+                _ => None,
+            });
+            if found.is_some() {
+                return found;
+            }
+        }
+        level = level
+            .iter()
+            .flat_map(|v| match v {
+                serde_json::Value::Object(map) => map.values().collect::<Vec<_>>(),
+                _ => Vec::new(),
+            })
+            .collect();
+    }
+    None
+}
+
+/// Start of a JSON value for error messages
+fn json_excerpt(value: &serde_json::Value) -> String {
+    let text = value.to_string();
+    match text.char_indices().nth(JSON_EXCERPT_LEN) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text,
+    }
+}
+
+/// Remote OS family with a well-known sftp-server location
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OsFamily {
+    /// Debian, Ubuntu and derivatives
+    Debian,
+    /// RHEL, CentOS, Fedora, Rocky, Alma, Amazon Linux
+    Rhel,
+    /// Alpine Linux
+    Alpine,
+    /// Arch Linux and derivatives
+    Arch,
+    /// FreeBSD
+    Freebsd,
+    /// macOS
+    Macos,
+}
+
+impl OsFamily {
+    /// Default sftp-server path for this OS family
+    pub fn sftp_server_path(self) -> &'static str {
+        match self {
+            OsFamily::Debian => DEFAULT_SFTP_SERVER,
+            OsFamily::Rhel => "/usr/libexec/openssh/sftp-server",
+            OsFamily::Alpine | OsFamily::Arch => "/usr/lib/ssh/sftp-server",
+            OsFamily::Freebsd | OsFamily::Macos => "/usr/libexec/sftp-server",
+        }
+    }
+
+    /// Identify the OS family from `uname -s` followed by /etc/os-release
+    fn detect(output: &str) -> Option<Self> {
+        let mut lines = output.lines();
+        match lines.next().map(str::trim) {
+            Some("Darwin") => return Some(OsFamily::Macos),
+            Some("FreeBSD") => return Some(OsFamily::Freebsd),
+            _ => {}
+        }
+
+        // ID first, then ID_LIKE, so e.g. Ubuntu (ID_LIKE=debian) is recognized
+        let mut ids: Vec<String> = Vec::new();
+        let mut likes: Vec<String> = Vec::new();
+        for line in lines {
+            if let Some((key, value)) = line.split_once('=') {
+                let value = value.trim().trim_matches('"').to_lowercase();
+                match key.trim() {
+                    "ID" => ids.push(value),
+                    "ID_LIKE" => likes.extend(value.split_whitespace().map(String::from)),
+                    _ => {}
+                }
+            }
+        }
+
+        ids.iter()
+            .chain(likes.iter())
+            .find_map(|id| match id.as_str() {
+                "debian" | "ubuntu" => Some(OsFamily::Debian),
+                "rhel" | "centos" | "fedora" | "rocky" | "almalinux" | "amzn" => {
+                    Some(OsFamily::Rhel)
+                }
+                "alpine" => Some(OsFamily::Alpine),
+                "arch" => Some(OsFamily::Arch),
+                _ => None,
+            })
+    }
+}
 
 #[derive(Debug, Deserialize)]
 pub struct TeleportStatusResponse {
@@ -18,26 +189,115 @@ pub struct TeleportActive {
     pub cluster: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct TeleportCluster {
+    cluster_name: String,
+}
+
+/// A `tsh ls` entry. Versions differ in where the name lives (`spec.hostname`,
+/// `metadata.name` or a top-level `hostname`/`name`), so everything is optional.
 #[derive(Debug, Deserialize)]
 struct TeleportNode {
+    #[serde(default)]
+    metadata: TeleportNodeMetadata,
+    #[serde(default)]
     spec: TeleportNodeSpec,
+    #[serde(default)]
+    hostname: Option<String>,
+    #[serde(default)]
+    name: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
+struct TeleportNodeMetadata {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    labels: HashMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
 struct TeleportNodeSpec {
-    hostname: String,
+    #[serde(default)]
+    hostname: Option<String>,
+    #[serde(default)]
+    cmd_labels: HashMap<String, TeleportCommandLabel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TeleportCommandLabel {
+    #[serde(default)]
+    result: String,
+}
+
+/// A node from `tsh ls` with its static and command labels
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub hostname: String,
+    pub labels: HashMap<String, String>,
 }
 
 impl Teleport {
     pub fn new() -> Self {
-        Self
+        Self { cluster: None }
+    }
+
+    /// Target a specific (e.g. leaf) cluster for node listing and scanning
+    pub fn with_cluster(cluster: Option<String>) -> Self {
+        Self { cluster }
+    }
+
+    /// Build a tsh command, adding `--cluster` when a cluster is selected.
+    /// Without automatic login, an expired session fails instead of prompting.
+    fn tsh(&self, args: &[&str]) -> Command {
+        let mut cmd = Command::new("tsh");
+        cmd.arg(args[0]);
+        if let Some(ref cluster) = self.cluster {
+            cmd.arg(format!("--cluster={}", cluster));
+        }
+        if !crate::interactive::can_login() {
+            cmd.arg("--no-relogin");
+        }
+        cmd.args(&args[1..]);
+        cmd
+    }
+
+    /// SSH command stored on imported items (and used verbatim as rclone's `ssh`)
+    pub fn ssh_command(&self, proxy: &str, hostname: &str) -> String {
+        match self.cluster {
+            Some(ref cluster) => format!(
+                "tsh ssh --proxy={} --cluster={} {}",
+                proxy, cluster, hostname
+            ),
+            None => format!("tsh ssh --proxy={} {}", proxy, hostname),
+        }
+    }
+
+    /// List the root and leaf clusters available via `tsh clusters`
+    pub fn list_clusters(&self) -> Result<Vec<String>> {
+        let output = Command::new("tsh")
+            .args(["clusters", "--format=json"])
+            .output()
+            .context("Failed to execute tsh clusters")?;
+
+        if !output.status.success() {
+            bail!(
+                "tsh clusters failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        let clusters: Vec<TeleportCluster> = serde_json::from_slice(&output.stdout)
+            .context("Failed to parse tsh clusters output")?;
+
+        Ok(clusters.into_iter().map(|c| c.cluster_name).collect())
     }
 
     /// Check if tsh is logged in and return status info.
     /// Returns an error if not logged in.
     pub fn get_status(&self) -> Result<TeleportActive> {
         self.try_get_status()?
-            .ok_or_else(|| anyhow::anyhow!("Not logged into Teleport. Run 'tsh login' first."))
+            .ok_or_else(|| ExitCode::Auth.error("Not logged into Teleport. Run 'tsh login' first."))
     }
 
     /// Try to get status without prompting for login
@@ -57,6 +317,19 @@ impl Teleport {
         Ok(response.active)
     }
 
+    /// Proxy for the stored tsh commands: a validated `override_addr`
+    /// (`--proxy` / `tsh_proxy`), else the one from `tsh status`
+    pub fn resolve_proxy(
+        &self,
+        status: &TeleportActive,
+        override_addr: Option<&str>,
+    ) -> Result<String> {
+        match override_addr {
+            Some(addr) => parse_proxy_addr(addr),
+            None => self.get_proxy(status),
+        }
+    }
+
     /// Extract proxy address from profile_url
     /// - "https://teleport.thedragon.dev:443" -> "teleport.thedragon.dev"
     /// - "https://proxy.example.com:3080" -> "proxy.example.com:3080"
@@ -78,9 +351,9 @@ impl Teleport {
     }
 
     /// List all nodes via `tsh ls --format=json`
-    pub fn list_nodes(&self) -> Result<Vec<String>> {
-        let output = Command::new("tsh")
-            .args(["ls", "--format=json"])
+    pub fn list_nodes(&self) -> Result<Vec<Node>> {
+        let output = self
+            .tsh(&["ls", "--format=json"])
             .output()
             .context("Failed to execute tsh ls")?;
 
@@ -88,36 +361,75 @@ impl Teleport {
             bail!("tsh ls failed: {}", String::from_utf8_lossy(&output.stderr));
         }
 
-        let nodes: Vec<TeleportNode> =
-            serde_json::from_slice(&output.stdout).context("Failed to parse tsh ls output")?;
-
-        Ok(nodes.into_iter().map(|n| n.spec.hostname).collect())
+        parse_nodes(&output.stdout)
     }
 
     /// Get SFTP subsystem path from remote node
-    /// Searches the filesystem for sftp-server binary
-    /// Returns the path (default: /usr/lib/openssh/sftp-server)
+    /// Detects the OS family and uses its known sftp-server path; only searches
+    /// the filesystem when the OS is unrecognized
+    /// Returns the path (default: /usr/lib/openssh/sftp-server when none is
+    /// found); fails when the node can't be reached over `tsh ssh`
     pub fn get_subsystem(&self, hostname: &str) -> Result<String> {
+        let os_output = self
+            .tsh(&["ssh", hostname, "uname -s; cat /etc/os-release 2>/dev/null"])
+            .output()
+            .context("Failed to detect OS on remote")?;
+
+        if os_output.status.success() {
+            if let Some(os) = OsFamily::detect(&String::from_utf8_lossy(&os_output.stdout)) {
+                return Ok(os.sftp_server_path().to_string());
+            }
+        }
+
         // Use find to locate sftp-server anywhere on the system
         let detect_script = r#"find /usr -name "sftp-server" -type f 2>/dev/null | head -1"#;
 
-        let output = Command::new("tsh")
-            .args(["ssh", hostname, detect_script])
+        let output = self
+            .tsh(&["ssh", hostname, detect_script])
             .output()
             .context("Failed to detect sftp-server on remote")?;
 
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!(
+                "tsh ssh failed: {}",
+                stderr.lines().next().unwrap_or("").trim()
+            );
+        }
+
         let stdout = String::from_utf8_lossy(&output.stdout);
         let path = stdout.trim();
 
-        if path.is_empty() || !output.status.success() {
+        if path.is_empty() {
             // Fallback to common default
-            Ok("/usr/lib/openssh/sftp-server".to_string())
+            Ok(DEFAULT_SFTP_SERVER.to_string())
         } else {
             Ok(path.to_string())
         }
     }
 }
 
+/// Validate a proxy address given as `host` or `host:port` (no scheme or path)
+/// and return it normalized
+pub fn parse_proxy_addr(addr: &str) -> Result<String> {
+    let addr = addr.trim();
+    let invalid = || anyhow::anyhow!("Invalid proxy '{}': expected host or host:port", addr);
+    if addr.is_empty() || addr.contains(['/', '@', '?', '#']) || addr.contains(char::is_whitespace)
+    {
+        return Err(invalid());
+    }
+
+    let url = Url::parse(&format!("ssh://{}", addr)).map_err(|_| invalid())?;
+    let host = url
+        .host_str()
+        .filter(|h| !h.is_empty())
+        .ok_or_else(invalid)?;
+    Ok(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    })
+}
+
 impl Default for Teleport {
     fn default() -> Self {
         Self::new()
@@ -0,0 +1,107 @@
+use std::fmt;
+use std::process::Command;
+
+/// A `major.minor.patch` tool version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Version {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Find the first version number in `--version` output, e.g.
+    /// "rclone v1.66.0" or "Teleport v16.4.2 git:v16.4.2-0 go1.22.7"
+    pub fn parse(output: &str) -> Option<Self> {
+        output.split_whitespace().find_map(|word| {
+            let word = word.strip_prefix('v').unwrap_or(word);
+            // Drop pre-release/build suffixes ("1.2.3-beta.1", "1.2.3+abc")
+            let core = word.split(['-', '+']).next()?;
+            let mut parts = core.split('.').map(|p| p.parse::<u32>().ok());
+            let major = parts.next()??;
+            let minor = parts.next()??;
+            let patch = parts.next().unwrap_or(Some(0))?;
+            Some(Self::new(major, minor, patch))
+        })
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// An external CLI this tool drives, with the oldest version known to work
+pub struct Tool {
+    pub name: &'static str,
+    version_args: &'static [&'static str],
+    pub minimum: Version,
+    /// What the minimum is needed for (shown in warnings)
+    pub reason: &'static str,
+}
+
+/// `item list --output json` and `item attachment download`
+pub const PASS_CLI: Tool = Tool {
+    name: "pass-cli",
+    version_args: &["--version"],
+    minimum: Version::new(1, 0, 0),
+    reason: "JSON item listing and attachment downloads",
+};
+
+/// `rclone config encryption set --password-command` (added in 1.64)
+pub const RCLONE: Tool = Tool {
+    name: "rclone",
+    version_args: &["--version"],
+    minimum: Version::new(1, 64, 0),
+    reason: "rclone config encryption set",
+};
+
+/// `tsh ls --format=json` with node labels and `tsh clusters --format=json`
+pub const TSH: Tool = Tool {
+    name: "tsh",
+    version_args: &["version"],
+    minimum: Version::new(10, 0, 0),
+    reason: "JSON node and cluster listing",
+};
+
+/// Every tool, in the order they are shown in the status view
+pub const TOOLS: &[Tool] = &[PASS_CLI, RCLONE, TSH];
+
+impl Tool {
+    /// Installed version (None if the tool is missing or its output has no version)
+    pub fn version(&self) -> Option<Version> {
+        let output = Command::new(self.name)
+            .args(self.version_args)
+            .output()
+            .ok()?;
+        Version::parse(&String::from_utf8_lossy(&output.stdout))
+            .or_else(|| Version::parse(&String::from_utf8_lossy(&output.stderr)))
+    }
+
+    /// Warning text if the installed version is older than `minimum`
+    pub fn outdated_warning(&self) -> Option<String> {
+        let version = self.version()?;
+        (version < self.minimum).then(|| {
+            format!(
+                "{} {} is older than {}, which is needed for {}; commands may fail. Please upgrade {}.",
+                self.name, version, self.minimum, self.reason, self.name
+            )
+        })
+    }
+
+    /// Print `outdated_warning` to stderr, if any
+    pub fn warn_if_outdated(&self) {
+        if let Some(warning) = self.outdated_warning() {
+            eprintln!("Warning: {}", warning);
+        }
+    }
+}